@@ -11,6 +11,7 @@ pub fn arg_matcher(arg: args::Args) -> Result<(), Report<Zerr>> {
             render::render(&arg, render)?;
             Ok(())
         }
+        args::Command::Watch(watch) => Ok(render::watch(&arg, watch)?),
         args::Command::Var(read_var) => Ok(var::read_var(&arg, read_var)?),
         args::Command::Init(init) => Ok(init::init(init)?),
         args::Command::ReplaceMatcher(replace) => Ok(replace_matcher::replace(&arg, replace)?),
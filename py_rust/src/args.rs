@@ -1,6 +1,6 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use clap::{command, Parser};
+use clap::Parser;
 use pyo3::prelude::*;
 
 use crate::{coerce::Coerce, prelude::*};
@@ -66,6 +66,9 @@ pub enum Command {
     /// Render all templates found whilst traversing the given root (default).
     Render(RenderCommand),
 
+    /// Render all templates, then keep watching the root, config and extensions, re-rendering on changes.
+    Watch(WatchCommand),
+
     /// Read a finalised context variable from the config file.
     Var(VarCommand),
 
@@ -90,12 +93,44 @@ pub enum Command {
     },
 }
 
+impl Command {
+    /// The render arguments, for the commands that render templates (render and watch).
+    pub fn render_args(&self) -> Option<&RenderCommand> {
+        match self {
+            Command::Render(render) => Some(render),
+            Command::Watch(watch) => Some(&watch.render),
+            _ => None,
+        }
+    }
+
+    /// Mutable version of render_args().
+    pub fn render_args_mut(&mut self) -> Option<&mut RenderCommand> {
+        match self {
+            Command::Render(render) => Some(render),
+            Command::Watch(watch) => Some(&mut watch.render),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, clap::Parser)]
 pub struct RenderCommand {
     /// The target directory to search and render.
+    ///
+    /// When a single template file is given instead, just that template is rendered with the current directory as the root.
     #[clap(default_value = ".")]
     pub root: PathBuf,
 
+    /// Only render these templates, or the templates inside these directories. Must be inside the root.
+    pub templates: Vec<PathBuf>,
+
+    /// Only render templates matching these git-style globs, matched relative to the root. E.g. --only 'services/api/**'
+    ///
+    /// Combines with any template paths given, templates matching either are rendered.
+    /// Lockfile entries of templates that aren't selected are left untouched.
+    #[arg(long)]
+    pub only: Vec<String>,
+
     /// No tasks will be run, cli vars will be ignored and treated as empty strings if "light" defaults not specified.
     #[arg(short, long, default_value = "false")]
     pub light: bool,
@@ -104,10 +139,38 @@ pub struct RenderCommand {
     #[arg(long, default_value = "false")]
     pub superlight: bool,
 
-    /// Force write all rendered files, ignore existing lockfile.
+    /// Force write all rendered files, ignore existing lockfile, overwriting any files zetch didn't write or that have been modified since.
     #[arg(short, long, default_value = "false")]
     pub force: bool,
 
+    /// Render all templates in memory and fail if any output would change, nothing is written to disk (including the lockfile).
+    ///
+    /// Outputs are compared against the real file contents, so manually edited outputs are also caught. Pre and post tasks won't be run.
+    ///
+    /// Useful in CI to make sure rendered files are up to date.
+    #[arg(long, default_value = "false")]
+    pub check: bool,
+
+    /// Print a colored unified diff of each output that changes, between its existing contents and the newly rendered.
+    ///
+    /// Combine with --check for a dry run that shows what would change without writing anything.
+    #[arg(long, default_value = "false")]
+    pub diff: bool,
+
+    /// Stop at the first template that fails to render, rather than rendering the rest and reporting every failure together.
+    #[arg(long, default_value = "false")]
+    pub fail_fast: bool,
+
+    /// Number of templates to render in parallel, defaults to the number of available cpus.
+    #[arg(short, long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub jobs: Option<usize>,
+
+    /// Write a json report of the render to this path, or '-' to print it to stdout in place of the summary line.
+    ///
+    /// Includes the status and duration of each template, tasks run, lockfile changes and timings. Written even when rendering fails.
+    #[arg(long)]
+    pub report: Option<PathBuf>,
+
     /// Comma separated list of env ctx vars to ignore defaults for and raise if not in env. E.g. --ban-defaults FOO,BAR...
     ///
     /// If no vars are provided, all defaults will be ignored.
@@ -120,11 +183,48 @@ pub struct RenderCommand {
     pub debug: bool,
 }
 
+impl RenderCommand {
+    /// Whether the report is printed in place of the summary with `--report -`, so nothing else can go to stdout.
+    pub fn report_to_stdout(&self) -> bool {
+        self.report
+            .as_deref()
+            .is_some_and(|target| target == Path::new("-"))
+    }
+
+    /// Clap fills the root first, so a lone template like `zetch render path/x.zetch.txt` needs moving over to the templates.
+    ///
+    /// Only when inside the current directory it would then be rendered from, otherwise the root is left to be reported as invalid.
+    pub fn template_as_root(&mut self) {
+        let inside_cwd = || match (self.root.canonicalize(), std::env::current_dir()) {
+            (Ok(root), Ok(cwd)) => cwd.canonicalize().is_ok_and(|cwd| root.starts_with(cwd)),
+            _ => false,
+        };
+        if self.templates.is_empty() && self.root.is_file() && inside_cwd() {
+            self.templates
+                .push(std::mem::replace(&mut self.root, PathBuf::from(".")));
+        }
+    }
+}
+
+#[derive(Clone, Debug, clap::Parser)]
+pub struct WatchCommand {
+    #[clap(flatten)]
+    pub render: RenderCommand,
+
+    /// Milliseconds to wait for further filesystem changes before re-rendering, batches up rapid saves.
+    #[arg(long, default_value = "200")]
+    pub debounce: u64,
+}
+
 #[derive(Clone, Debug, clap::Parser)]
 pub struct ReplaceMatcherCommand {
-    #[clap(help = "The old matcher in template filenames to look for. E.g. 'jinja'.")]
+    #[clap(
+        help = "The old matcher in template filenames to look for, or the pattern of one of the config's match_rules. E.g. 'jinja' or '*.j2'."
+    )]
     pub old_matcher: String,
-    #[clap(help = "The new matcher to replace the old in each template filename. E.g. 'zetch'.")]
+    #[clap(
+        help = "The new matcher to replace the old in each template filename, or the pattern of one of the config's match_rules. E.g. 'zetch'."
+    )]
     pub new_matcher: String,
 }
 
@@ -2,12 +2,17 @@ use std::{fs, path::Path};
 
 use serde::{Deserialize, Serialize};
 
-use super::{context::Context, engine::Engine, tasks::Tasks};
+use super::{
+    context::Context, engine::Engine, formatters::Formatter, match_rules::MatchRule, modes::Mode,
+    tasks::Tasks,
+};
 use crate::{init::update_schema_directive_if_needed, prelude::*};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Config {
     // All should be optional to allow empty config file, even though it wouldn't make too much sense!
+    #[serde(default = "Vec::new")]
+    pub allow_overwrite: Vec<String>,
     #[serde(default = "Context::default")]
     pub context: Context,
     #[serde(default = "Vec::new")]
@@ -15,38 +20,70 @@ pub struct Config {
     #[serde(default = "Engine::default")]
     pub engine: Engine,
     #[serde(default = "Vec::new")]
+    pub formatters: Vec<Formatter>,
+    #[serde(default = "Vec::new")]
     pub ignore_files: Vec<String>,
+    #[serde(default = "Vec::new")]
+    pub match_rules: Vec<MatchRule>,
     #[serde(default = "default_matchers")]
     pub matchers: Vec<String>,
+    #[serde(default = "Vec::new")]
+    pub modes: Vec<Mode>,
+    #[serde(default = "default_orphans")]
+    pub orphans: Orphans,
+    #[serde(default = "Vec::new")]
+    pub regions: Vec<String>,
     #[serde(default = "Tasks::default")]
     pub tasks: Tasks,
+    #[serde(default = "Vec::new")]
+    pub validate: Vec<String>,
+    #[serde(default = "Vec::new")]
+    pub verbatim: Vec<String>,
+}
+
+/// What to do with a previously rendered output when its template no longer exists.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Orphans {
+    /// Delete the output, as long as it hasn't been modified since last rendered.
+    Delete,
+    /// Leave the output but warn about it.
+    Warn,
+    /// Silently leave the output.
+    Keep,
 }
 
 fn default_matchers() -> Vec<String> {
     vec!["zetch".into()]
 }
 
+fn default_orphans() -> Orphans {
+    // NOTE: when changing make sure to update schema.json default for config hinting
+    Orphans::Delete
+}
+
 impl Config {
     pub fn ctx_keys(&self) -> Vec<&str> {
         let mut keys = Vec::new();
 
-        for (key, _) in self.context.stat.iter() {
+        for key in self.context.stat.keys() {
             keys.push(key.as_str());
         }
 
-        for (key, _) in self.context.env.iter() {
+        for key in self.context.env.keys() {
             keys.push(key.as_str());
         }
 
-        for (key, _) in self.context.cli.iter() {
+        for key in self.context.cli.keys() {
             keys.push(key.as_str());
         }
 
         keys
     }
 
-    pub fn from_toml(config_path: &Path) -> Result<Self, Report<Zerr>> {
-        Config::from_toml_inner(config_path).attach_printable_lazy(|| {
+    /// Read the config, saving any managed sections that need updating unless write is false, e.g. in check mode.
+    pub fn from_toml(config_path: &Path, write: bool) -> Result<Self, Report<Zerr>> {
+        Config::from_toml_inner(config_path, write).attach_printable_lazy(|| {
             format!(
                 "Error reading config file from '{}'.",
                 config_path.display()
@@ -54,8 +91,8 @@ impl Config {
         })
     }
 
-    fn from_toml_inner(config_path: &Path) -> Result<Self, Report<Zerr>> {
-        let contents = autoupdate(config_path)?;
+    fn from_toml_inner(config_path: &Path, write: bool) -> Result<Self, Report<Zerr>> {
+        let contents = autoupdate(config_path, write)?;
 
         // Decode directly the toml directly into serde/json, using that internally:
         let json: serde_json::Value = match toml::from_str(&contents) {
@@ -86,7 +123,8 @@ impl Config {
 /// Reads & pre-parses the config and updates managed sections, returns updated to save and use if changes needed.
 ///
 /// E.g. currently just updates the schema directive if needs changing.
-fn autoupdate(config_path: &Path) -> Result<String, Report<Zerr>> {
+/// The updates are still used when not written.
+fn autoupdate(config_path: &Path, write: bool) -> Result<String, Report<Zerr>> {
     let mut contents = fs::read_to_string(config_path).change_context(Zerr::InternalError)?;
     let mut updated = false;
 
@@ -97,7 +135,7 @@ fn autoupdate(config_path: &Path) -> Result<String, Report<Zerr>> {
         updated = true;
     }
 
-    if updated {
+    if updated && write {
         fs::write(config_path, &contents).change_context(Zerr::InternalError)?;
     }
 
@@ -1,5 +1,9 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::prelude::*;
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Engine {
     #[serde(default = "default_block_start")]
@@ -16,6 +20,102 @@ pub struct Engine {
     pub comment_end: String,
     #[serde(default = "default_custom_extensions")]
     pub custom_extensions: Vec<String>,
+    #[serde(default = "default_template_dirs")]
+    pub template_dirs: Vec<String>,
+    #[serde(default = "default_relative_includes")]
+    pub relative_includes: bool,
+    #[serde(default = "default_preload")]
+    pub preload: Vec<Preload>,
+    #[serde(default = "default_trim_blocks")]
+    pub trim_blocks: bool,
+    #[serde(default = "default_lstrip_blocks")]
+    pub lstrip_blocks: bool,
+    #[serde(default = "default_undefined")]
+    pub undefined: Undefined,
+    #[serde(default = "default_recursion_limit")]
+    pub recursion_limit: usize,
+    #[serde(default = "default_fuel")]
+    pub fuel: Option<u64>,
+    #[serde(default = "default_overrides")]
+    pub overrides: Vec<EngineOverride>,
+    #[serde(default = "default_autoescape")]
+    pub autoescape: BTreeMap<String, Autoescape>,
+}
+
+/// How interpolated values are escaped in templates whose outputs have a certain extension, the safe filter skips it.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Autoescape {
+    /// Values are serialized to json, so strings come out quoted.
+    Json,
+    /// For inside a double quoted yaml string, e.g. key: "{{ value }}".
+    YamlString,
+    /// For inside a double quoted (basic) toml string, e.g. key = "{{ value }}".
+    TomlString,
+    /// Values are quoted as a single shell word where needed.
+    Shell,
+}
+
+impl Autoescape {
+    pub const ALL: [Autoescape; 4] = [
+        Autoescape::Json,
+        Autoescape::YamlString,
+        Autoescape::TomlString,
+        Autoescape::Shell,
+    ];
+
+    /// The name as used in the config.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Autoescape::Json => "json",
+            Autoescape::YamlString => "yaml-string",
+            Autoescape::TomlString => "toml-string",
+            Autoescape::Shell => "shell",
+        }
+    }
+}
+
+/// Engine options for the templates matching its globs, e.g. a different syntax for files natively using {{ }}.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EngineOverride {
+    /// Matched against the template paths relative to the root.
+    pub globs: Vec<String>,
+    /// Replacing the same options from the rest of [engine].
+    #[serde(flatten)]
+    pub options: serde_json::Map<String, serde_json::Value>,
+}
+
+/// How undefined variables are treated when rendering.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Undefined {
+    /// Any use of an undefined variable errors.
+    Strict,
+    /// Undefined variables and their attributes render as empty, only erroring when iterated or compared.
+    Chainable,
+    /// Undefined variables render as empty, but their attributes error.
+    Lenient,
+}
+
+impl From<Undefined> for minijinja::UndefinedBehavior {
+    fn from(undefined: Undefined) -> Self {
+        match undefined {
+            Undefined::Strict => minijinja::UndefinedBehavior::Strict,
+            Undefined::Chainable => minijinja::UndefinedBehavior::Chainable,
+            Undefined::Lenient => minijinja::UndefinedBehavior::Lenient,
+        }
+    }
+}
+
+/// A template whose macros and top-level set values are made globals, so templates can use them without importing.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preload {
+    /// Looked up the same as the names used in import.
+    pub template: String,
+    /// When set the exports are only available under this name, e.g. c.header(), otherwise each is its own global.
+    #[serde(rename = "as", default)]
+    pub alias: Option<String>,
 }
 
 impl Engine {
@@ -29,7 +129,53 @@ impl Engine {
             comment_start: default_comment_start(),
             comment_end: default_comment_end(),
             custom_extensions: default_custom_extensions(),
+            template_dirs: default_template_dirs(),
+            relative_includes: default_relative_includes(),
+            preload: default_preload(),
+            trim_blocks: default_trim_blocks(),
+            lstrip_blocks: default_lstrip_blocks(),
+            undefined: default_undefined(),
+            recursion_limit: default_recursion_limit(),
+            fuel: default_fuel(),
+            overrides: default_overrides(),
+            autoescape: default_autoescape(),
+        }
+    }
+
+    /// A copy with some of the options replaced, only those that can differ between templates.
+    pub fn with_options(
+        &self,
+        options: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<Self, Report<Zerr>> {
+        let serde_json::Value::Object(mut merged) =
+            serde_json::to_value(self).change_context(Zerr::InternalError)?
+        else {
+            return Err(zerr!(
+                Zerr::InternalError,
+                "Engine didn't serialize to a map."
+            ));
+        };
+        for (key, value) in options.iter() {
+            // Extensions, search paths and preloads are loaded once for the whole render, overrides can't nest:
+            if matches!(
+                key.as_str(),
+                "custom_extensions"
+                    | "template_dirs"
+                    | "relative_includes"
+                    | "preload"
+                    | "overrides"
+            ) || !merged.contains_key(key)
+            {
+                return Err(zerr!(
+                    Zerr::ConfigInvalid,
+                    "'{}' isn't an engine option that can be set per template.",
+                    key
+                ));
+            }
+            merged.insert(key.clone(), value.clone());
         }
+        serde_json::from_value(serde_json::Value::Object(merged))
+            .change_context(Zerr::ConfigInvalid)
     }
 }
 
@@ -67,3 +213,53 @@ fn default_custom_extensions() -> Vec<String> {
     // NOTE: when changing make sure to update schema.json default for config hinting
     vec![]
 }
+
+fn default_template_dirs() -> Vec<String> {
+    // NOTE: when changing make sure to update schema.json default for config hinting
+    vec![]
+}
+
+fn default_relative_includes() -> bool {
+    // NOTE: when changing make sure to update schema.json default for config hinting
+    false
+}
+
+fn default_preload() -> Vec<Preload> {
+    // NOTE: when changing make sure to update schema.json default for config hinting
+    vec![]
+}
+
+fn default_trim_blocks() -> bool {
+    // NOTE: when changing make sure to update schema.json default for config hinting
+    false
+}
+
+fn default_lstrip_blocks() -> bool {
+    // NOTE: when changing make sure to update schema.json default for config hinting
+    false
+}
+
+fn default_undefined() -> Undefined {
+    // NOTE: when changing make sure to update schema.json default for config hinting
+    Undefined::Strict
+}
+
+fn default_recursion_limit() -> usize {
+    // NOTE: when changing make sure to update schema.json default for config hinting
+    500
+}
+
+fn default_fuel() -> Option<u64> {
+    // NOTE: when changing make sure to update schema.json default for config hinting
+    None
+}
+
+fn default_overrides() -> Vec<EngineOverride> {
+    // NOTE: when changing make sure to update schema.json default for config hinting
+    vec![]
+}
+
+fn default_autoescape() -> BTreeMap<String, Autoescape> {
+    // NOTE: when changing make sure to update schema.json default for config hinting
+    BTreeMap::new()
+}
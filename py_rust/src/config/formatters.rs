@@ -0,0 +1,73 @@
+use std::{io::Write, path::Path};
+
+use bitbazaar::cli::{Bash, BashErr};
+use serde::{Deserialize, Serialize};
+
+use super::tasks::IN_TASK_ENV_VAR;
+use crate::prelude::*;
+
+/// Formatters are given the absolute path of the output being formatted in this env var, e.g. for prettier's --stdin-filepath.
+pub static OUTPUT_PATH_ENV_VAR: &str = "ZETCH_OUTPUT_PATH";
+
+/// The temporary file holding the contents to format, piped into the formatter's stdin.
+static INPUT_PATH_ENV_VAR: &str = "ZETCH_FORMAT_INPUT";
+
+/// A command that formats rendered outputs matching its globs before they're hashed and written.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Formatter {
+    pub globs: Vec<String>,
+    pub command: String,
+}
+
+impl Formatter {
+    /// Pass the contents to the command on stdin, returning its stdout. Run from the config file's directory.
+    pub fn format(
+        &self,
+        config_filepath: &Path,
+        out_path: &Path,
+        contents: &str,
+    ) -> Result<String, Report<Zerr>> {
+        let config_dir = config_filepath.parent().ok_or_else(|| {
+            zerr!(
+                Zerr::InternalError,
+                "Failed to get parent dir of config file: {}",
+                config_filepath.display()
+            )
+        })?;
+
+        // The runner can't be given stdin directly, so the contents are piped in from a temporary file:
+        let mut input = tempfile::NamedTempFile::new().change_context(Zerr::InternalError)?;
+        input
+            .write_all(contents.as_bytes())
+            .change_context(Zerr::InternalError)?;
+
+        let bash = Bash::new()
+            .chdir(config_dir)
+            .env(IN_TASK_ENV_VAR, "1")
+            .env(OUTPUT_PATH_ENV_VAR, out_path.display().to_string())
+            .env(INPUT_PATH_ENV_VAR, input.path().display().to_string())
+            .cmd(format!("cat \"${INPUT_PATH_ENV_VAR}\" | {}", self.command));
+
+        let cmd_out = match bash.run() {
+            Ok(cmd_out) => Ok(cmd_out),
+            Err(e) => match e.current_context() {
+                BashErr::InternalError(_) => Err(e.change_context(Zerr::InternalError)),
+                _ => Err(e.change_context(Zerr::UserCommandError)),
+            },
+        }
+        .attach_printable_lazy(|| format!("Failed to run formatter '{}'.", self.command))?;
+
+        if !cmd_out.success() {
+            return Err(zerr!(
+                Zerr::UserCommandError,
+                "Formatter '{}' failed with exit code {} formatting '{}'.",
+                self.command,
+                cmd_out.code(),
+                out_path.display()
+            )
+            .attach_printable(cmd_out.stderr()));
+        }
+
+        Ok(cmd_out.stdout())
+    }
+}
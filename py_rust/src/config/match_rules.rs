@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+/// Identifies templates by a name pattern rather than a matcher, for existing conventions like "*.j2" or "_template_*".
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MatchRule {
+    /// A file or directory name with a single "*", e.g. "*.in" matching "config.h.in".
+    pub pattern: String,
+    /// The output's name, "*" replaced with what it matched in the pattern.
+    #[serde(default = "default_out")]
+    pub out: String,
+}
+
+fn default_out() -> String {
+    // NOTE: when changing make sure to update schema.json default for config hinting
+    "*".into()
+}
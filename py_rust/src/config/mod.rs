@@ -1,6 +1,9 @@
 pub mod conf;
 pub mod context;
 pub mod engine;
+pub mod formatters;
+pub mod match_rules;
+pub mod modes;
 mod static_var;
 pub mod tasks;
 mod validate;
@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+/// Explicit permissions for outputs matching its globs, rather than copying the template's.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Mode {
+    pub globs: Vec<String>,
+    /// Octal, e.g. "755".
+    pub mode: String,
+}
+
+/// Parse octal permissions like "755", None when invalid.
+pub fn parse_mode(mode: &str) -> Option<u32> {
+    if mode.is_empty() || !mode.chars().all(|c| ('0'..='7').contains(&c)) {
+        return None;
+    }
+    u32::from_str_radix(mode, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+}
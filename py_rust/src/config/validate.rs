@@ -61,7 +61,33 @@ pub fn post_validate(conf: &mut Config, config_path: &Path) -> Result<(), Report
         }
     }
 
-    // ignore_files and engine.custom_extensions should be resolved relative to the config file, so rewrite the paths if needed and make sure they exist:
+    // Rules need a single "*", with something fixed around it in the pattern so it doesn't match every file:
+    for rule in conf.match_rules.iter() {
+        if rule.pattern.matches('*').count() != 1 || rule.pattern.len() < 2 {
+            return Err(zerr!(
+                Zerr::ConfigInvalid,
+                "[match_rules]: patterns need a single '*' and something around it, e.g. '*.j2'. Not: '{}'",
+                rule.pattern
+            ));
+        }
+        if rule.out.matches('*').count() != 1 {
+            return Err(zerr!(
+                Zerr::ConfigInvalid,
+                "[match_rules]: out needs a single '*' to put the matched part of the name, e.g. '*'. Not: '{}'",
+                rule.out
+            ));
+        }
+        if rule.pattern.contains('/') || rule.out.contains('/') {
+            return Err(zerr!(
+                Zerr::ConfigInvalid,
+                "[match_rules]: patterns and out are file or directory names, they can't contain '/'. Not: '{}' -> '{}'",
+                rule.pattern,
+                rule.out
+            ));
+        }
+    }
+
+    // ignore_files, engine.custom_extensions and engine.template_dirs should be resolved relative to the config file, so rewrite the paths if needed and make sure they exist:
     let validate_and_rewrite = |in_path: String| -> Result<String, Report<Zerr>> {
         // Make relative to config file if not absolute:
         let path = if !PathBuf::from(&in_path).is_absolute() {
@@ -124,6 +150,24 @@ pub fn post_validate(conf: &mut Config, config_path: &Path) -> Result<(), Report
         }
     }
 
+    for engine_override in conf.engine.overrides.iter() {
+        conf.engine
+            .with_options(&engine_override.options)
+            .attach_printable("[engine.overrides]: invalid options.")?;
+    }
+
+    for template_dir in conf.engine.template_dirs.iter_mut() {
+        *template_dir = validate_and_rewrite(template_dir.clone())?;
+
+        if !PathBuf::from(&template_dir).is_dir() {
+            return Err(zerr!(
+                Zerr::ConfigInvalid,
+                "Template dir '{}' is not a directory.",
+                template_dir
+            ));
+        }
+    }
+
     Ok(())
 }
 
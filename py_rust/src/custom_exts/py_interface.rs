@@ -139,6 +139,57 @@ pub fn load_custom_exts(
     Ok(std::mem::take(&mut *PY_USER_FUNCS.lock()))
 }
 
+/// Reset the loaded custom extensions, allowing load_custom_exts() to be called again.
+///
+/// The extension modules are removed from sys.modules so they'll be re-imported fresh, picking up any changes.
+pub fn unload_custom_exts(exts: &[String]) -> Result<(), Report<Zerr>> {
+    *PY_CONTEXT.lock() = None;
+    PY_USER_FUNCS.lock().clear();
+
+    if exts.is_empty() {
+        return Ok(());
+    }
+
+    Python::with_gil(|py| {
+        let modules = py
+            .import("sys")
+            .change_context(Zerr::InternalError)?
+            .getattr("modules")
+            .change_context(Zerr::InternalError)?;
+        let modules = modules.downcast::<PyDict>().map_err(|e| {
+            zerr!(
+                Zerr::InternalError,
+                "Failed to get sys.modules whilst unloading custom extensions: '{}'",
+                e
+            )
+        })?;
+
+        for extension_path in exts.iter() {
+            let name = match Path::new(extension_path).file_stem() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => continue,
+            };
+            let submodule_prefix = format!("{name}.");
+
+            // Packages may have also imported submodules, which need removing too:
+            let mut to_remove = vec![];
+            for key in modules.keys() {
+                let key = key
+                    .extract::<String>()
+                    .change_context(Zerr::InternalError)?;
+                if key == name || key.starts_with(&submodule_prefix) {
+                    to_remove.push(key);
+                }
+            }
+            for key in to_remove {
+                modules.del_item(key).change_context(Zerr::InternalError)?;
+            }
+        }
+
+        Ok(())
+    })
+}
+
 pub fn mini_values_to_py_params(
     py: Python,
     values: minijinja::value::Rest<minijinja::Value>,
@@ -20,6 +20,12 @@ pub enum Zerr {
     CustomPyFunctionError,
     /// An error that occurred whilst rendering templates, should be a problem with the user supplied templates, not internal.
     RenderTemplateError,
+    /// When running render with --check and some outputs would change.
+    CheckFailed,
+    /// When rendering would overwrite a file zetch didn't write, or one that's been modified since it was last rendered.
+    OverwriteRefused,
+    /// When the filesystem watcher used by the watch command fails or is misused.
+    WatchError,
     /// When a variable requested using subcommand "var" doesn't exist.
     ReadVarMissing,
     /// When the file subcommand is called incorrectly.
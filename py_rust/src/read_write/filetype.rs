@@ -14,7 +14,17 @@ pub enum FileType {
 pub static VALID_FILE_EXTS_AND_OPTS: &[&str] = &["json", "yaml", "yml", "toml"];
 
 impl FileType {
-    fn validate_file(&self, contents: &str) -> Result<(), Report<Zerr>> {
+    /// The filetype of a file extension, None when not a supported filetype.
+    pub fn from_ext(ext: &str) -> Option<Self> {
+        match ext {
+            "json" => Some(FileType::Json),
+            "yaml" | "yml" => Some(FileType::Yaml),
+            "toml" => Some(FileType::Toml),
+            _ => None,
+        }
+    }
+
+    pub fn validate_file(&self, contents: &str) -> Result<(), Report<Zerr>> {
         match self {
             FileType::Json => {
                 // Using fjson rather than serde to allow c-style comments in json files:
@@ -90,12 +100,7 @@ pub fn get_filetype(
                 )
             })?;
 
-            match ext {
-                "json" => Some(FileType::Json),
-                "yaml" | "yml" => Some(FileType::Yaml),
-                "toml" => Some(FileType::Toml),
-                _ => None,
-            }
+            FileType::from_ext(ext)
         } else {
             None
         };
@@ -189,10 +189,7 @@ impl<'t, 'r> Traversable<'r> for TomlTraverser<'t> {
                 _ => Err(zerr_int!()),
             },
             Inner::Item(item) => match &item {
-                Item::Value(val) => match val {
-                    toml_edit::Value::Array(arr) => Ok(arr.len()),
-                    _ => Err(zerr_int!()),
-                },
+                Item::Value(toml_edit::Value::Array(arr)) => Ok(arr.len()),
                 Item::ArrayOfTables(arr) => Ok(arr.len()),
                 _ => Err(zerr_int!()),
             },
@@ -247,13 +244,10 @@ impl<'t, 'r> Traversable<'r> for TomlTraverser<'t> {
                 _ => Err(zerr_int!()),
             },
             Inner::Item(item) => match item.borrow_mut() {
-                Item::Value(val) => match val {
-                    toml_edit::Value::Array(arr) => {
-                        arr.remove(index);
-                        Ok(())
-                    }
-                    _ => Err(zerr_int!()),
-                },
+                Item::Value(toml_edit::Value::Array(arr)) => {
+                    arr.remove(index);
+                    Ok(())
+                }
                 Item::ArrayOfTables(arr) => {
                     arr.remove(index);
                     Ok(())
@@ -9,3 +9,4 @@ mod traverser;
 mod utils;
 
 pub use entry::handle_file_cmd;
+pub use filetype::FileType;
@@ -8,6 +8,7 @@ pub struct Debug {
     pub ctx: HashMap<String, serde_json::Value>,
     pub written: Vec<String>,
     pub identical: Vec<String>,
+    pub skipped: Vec<String>,
     pub matched_templates: Vec<String>,
     pub lockfile_modified: bool,
 }
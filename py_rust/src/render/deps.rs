@@ -0,0 +1,308 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use parking_lot::Mutex;
+
+use super::{loader::SearchPaths, lockfile::hash_contents, regions, template::Template};
+use crate::{prelude::*, state::State};
+
+/// Custom functions can read the whole context through zetch.context(), so templates calling them depend on all of it.
+static WHOLE_CTX_KEY: &str = "zetch.context()";
+
+/// The inputs a template was last rendered with, stored in the lockfile.
+///
+/// When none of these have changed, rendering again would produce the same output so the template can be skipped.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Deps {
+    /// Hash of the template's own source, excluding the rendered bodies for files with templated regions.
+    pub source: String,
+    /// Hash of everything that affects all templates: engine config, formatters, custom extension sources and env defaults.
+    pub engine: String,
+    /// Hashes of the files pulled in through include/import/extends, directly or nested, by name as written in the templates.
+    #[serde(serialize_with = "crate::utils::ordered_map_serializer")]
+    pub includes: HashMap<String, String>,
+    /// Hashes of the values of the context variables read by the template and its includes.
+    #[serde(serialize_with = "crate::utils::ordered_map_serializer")]
+    pub ctx: HashMap<String, String>,
+}
+
+/// Tracks the inputs of templates whilst rendering.
+///
+/// The environment reports every include/import/extends through its path join callback,
+/// which is recorded here as a graph so a template's nested includes can be found after rendering it.
+#[derive(Debug, Clone)]
+pub struct Tracker {
+    search: SearchPaths,
+    engine: String,
+    includes: Arc<Mutex<HashMap<String, HashSet<String>>>>,
+    custom_funcs: HashSet<String>,
+}
+
+impl Tracker {
+    pub fn new(root: &Path, state: &State) -> Result<Self, Report<Zerr>> {
+        let search = SearchPaths::new(root, state);
+        Ok(Self {
+            engine: engine_hash(state, &search)?,
+            search,
+            includes: Arc::new(Mutex::new(HashMap::new())),
+            custom_funcs: HashSet::new(),
+        })
+    }
+
+    /// Record the parent template pulling in another, used as the env's path join callback.
+    pub fn record_include(&self, name: &str, parent: &str) {
+        self.includes
+            .lock()
+            .entry(parent.to_string())
+            .or_default()
+            .insert(name.to_string());
+    }
+
+    /// Record a custom python function registered with the env.
+    pub fn add_custom_func(&mut self, name: &str) {
+        self.custom_funcs.insert(name.to_string());
+    }
+
+    /// Whether the inputs recorded for the template's last render are all unchanged.
+    pub fn is_fresh(
+        &self,
+        state: &State,
+        template: &Template,
+        recorded: &Deps,
+    ) -> Result<bool, Report<Zerr>> {
+        if recorded.engine != self.engine || recorded.source != source_hash(template)? {
+            return Ok(false);
+        }
+        for (name, hash) in recorded.includes.iter() {
+            if hash != &self.hash_include(name)? {
+                return Ok(false);
+            }
+        }
+        for (key, hash) in recorded.ctx.iter() {
+            if Some(hash) != ctx_hash(state, key)?.as_ref() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Whether any of the recorded includes are among the changed paths, so templates including a changed template are re-rendered too.
+    pub fn includes_changed(&self, recorded: &Deps, changed: &HashSet<PathBuf>) -> bool {
+        recorded
+            .includes
+            .keys()
+            .any(|name| match self.search.resolve(name) {
+                Some(path) => {
+                    changed.contains(&path)
+                        || path
+                            .canonicalize()
+                            .is_ok_and(|path| changed.contains(&path))
+                }
+                // Deleted since, the freshness check will decide:
+                None => true,
+            })
+    }
+
+    /// Collect the inputs of a template that's just been rendered.
+    pub fn collect(
+        &self,
+        env: &minijinja::Environment,
+        state: &State,
+        template: &Template,
+    ) -> Result<Deps, Report<Zerr>> {
+        // Copied as they are, so only the source matters:
+        if template.verbatim {
+            return Ok(Deps {
+                source: source_hash(template)?,
+                engine: self.engine.clone(),
+                includes: HashMap::new(),
+                ctx: HashMap::new(),
+            });
+        }
+
+        let include_names = self.all_includes(&template.rel_path);
+
+        let mut includes = HashMap::new();
+        for name in include_names.iter() {
+            includes.insert(name.clone(), self.hash_include(name)?);
+        }
+
+        // Static analysis of which variables are read, conservative as it includes all branches:
+        let mut vars = HashSet::new();
+        // Front matter expressions, the items of a foreach are all from its expression's variables:
+        for expr in [&template.front.skip_if, &template.front.foreach]
+            .into_iter()
+            .flatten()
+        {
+            let expr = env
+                .compile_expression(expr)
+                .change_context(Zerr::InternalError)?;
+            vars.extend(expr.undeclared_variables(false));
+        }
+        if template.regions {
+            let contents =
+                fs::read_to_string(&template.path).change_context(Zerr::InternalError)?;
+            for region in regions::parse(&contents)? {
+                let tmpl = env
+                    .template_from_named_str(&template.rel_path, &region.source)
+                    .change_context(Zerr::InternalError)?;
+                vars.extend(tmpl.undeclared_variables(false));
+            }
+        }
+        let names = if template.regions {
+            include_names.iter().collect::<Vec<_>>()
+        } else {
+            std::iter::once(&template.rel_path)
+                .chain(include_names.iter())
+                .collect()
+        };
+        for name in names {
+            // Includes that failed to load would have failed the render, unless ignored as missing:
+            let Ok(tmpl) = env.get_template(name) else {
+                continue;
+            };
+            vars.extend(tmpl.undeclared_variables(false));
+        }
+
+        let mut ctx = HashMap::new();
+        for var in vars {
+            let key = if self.custom_funcs.contains(&var) {
+                WHOLE_CTX_KEY
+            } else {
+                var.as_str()
+            };
+            if let Some(hash) = ctx_hash(state, key)? {
+                ctx.insert(key.to_string(), hash);
+            }
+        }
+
+        Ok(Deps {
+            source: source_hash(template)?,
+            engine: self.engine.clone(),
+            includes,
+            ctx,
+        })
+    }
+
+    /// Hash of an include by name, an empty string when it can't be found, e.g. one marked as ignore missing.
+    fn hash_include(&self, name: &str) -> Result<String, Report<Zerr>> {
+        match self.search.resolve(name) {
+            Some(path) => hash_file(&path),
+            None => Ok("".to_string()),
+        }
+    }
+
+    /// All templates pulled in by the given template, directly or through other includes.
+    fn all_includes(&self, name: &str) -> Vec<String> {
+        let graph = self.includes.lock();
+        let mut seen = HashSet::new();
+        let mut stack = vec![name.to_string()];
+        while let Some(current) = stack.pop() {
+            if let Some(children) = graph.get(&current) {
+                for child in children.iter() {
+                    if child != name && seen.insert(child.clone()) {
+                        stack.push(child.clone());
+                    }
+                }
+            }
+        }
+        let mut all = seen.into_iter().collect::<Vec<_>>();
+        all.sort();
+        all
+    }
+}
+
+/// Hash of a file's contents, an empty string when it doesn't exist, e.g. an include marked as ignore missing.
+fn hash_file(path: &Path) -> Result<String, Report<Zerr>> {
+    match fs::read(path) {
+        Ok(contents) => Ok(hash_contents(&contents)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok("".to_string()),
+        Err(e) => Err(e).change_context(Zerr::InternalError),
+    }
+}
+
+/// Hash of a template's source. For files with templated regions everything but the region bodies is the source,
+/// so writing the rendered regions doesn't make the file look changed.
+fn source_hash(template: &Template) -> Result<String, Report<Zerr>> {
+    if !template.regions {
+        return hash_file(&template.path);
+    }
+    let contents = match fs::read_to_string(&template.path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok("".to_string()),
+        Err(e) => return Err(e).change_context(Zerr::InternalError),
+    };
+    Ok(match regions::parse(&contents) {
+        Ok(parsed) => hash_contents(regions::skeleton(&contents, &parsed)),
+        // Will fail when rendered:
+        Err(_) => hash_contents(&contents),
+    })
+}
+
+/// Hash of a context variable's current value, None when it's not a context variable (e.g. a builtin or loop var).
+fn ctx_hash(state: &State, key: &str) -> Result<Option<String>, Report<Zerr>> {
+    let value = if key == WHOLE_CTX_KEY {
+        serde_json::to_string(&state.ctx.iter().collect::<BTreeMap<_, _>>())
+    } else if let Some(value) = state.ctx.get(key) {
+        serde_json::to_string(value)
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(hash_contents(
+        &value.change_context(Zerr::InternalError)?,
+    )))
+}
+
+/// Hash everything affecting the output of all templates, when changed nothing can be skipped.
+fn engine_hash(state: &State, search: &SearchPaths) -> Result<String, Report<Zerr>> {
+    let mut extensions = BTreeMap::new();
+    for extension in state.conf.engine.custom_extensions.iter() {
+        hash_extension(Path::new(extension), &mut extensions)?;
+    }
+
+    // Preloaded templates are available to all templates:
+    let mut preloads = BTreeMap::new();
+    for preload in state.conf.engine.preload.iter() {
+        if let Some(path) = search.resolve(&preload.template) {
+            preloads.insert(&preload.template, hash_file(&path)?);
+        }
+    }
+
+    let env_defaults = state
+        .conf
+        .context
+        .env
+        .iter()
+        .map(|(key, var)| (key, &var.default))
+        .collect::<BTreeMap<_, _>>();
+
+    Ok(hash_contents(
+        &serde_json::to_string(&(
+            &state.conf.engine,
+            &state.conf.formatters,
+            &state.conf.validate,
+            &state.conf.verbatim,
+            env_defaults,
+            extensions,
+            preloads,
+            state.superlight,
+        ))
+        .change_context(Zerr::InternalError)?,
+    ))
+}
+
+/// Hash the python sources of a custom extension, which may be a single file or a package.
+fn hash_extension(path: &Path, hashes: &mut BTreeMap<String, String>) -> Result<(), Report<Zerr>> {
+    if path.is_dir() {
+        for entry in fs::read_dir(path).change_context(Zerr::InternalError)? {
+            hash_extension(&entry.change_context(Zerr::InternalError)?.path(), hashes)?;
+        }
+    } else if path.extension().is_some_and(|ext| ext == "py") {
+        hashes.insert(path.display().to_string(), hash_file(path)?);
+    }
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use colored::Colorize;
+use similar::{ChangeTag, TextDiff};
+
+/// Format a colored unified diff between an output's existing contents (None when it doesn't exist yet) and its newly rendered contents.
+pub fn unified_diff(out_rel_path: &str, existing: Option<&str>, compiled: &str) -> String {
+    let diff = TextDiff::from_lines(existing.unwrap_or(""), compiled);
+
+    let mut s = format!(
+        "{}\n{}\n",
+        if existing.is_some() {
+            format!("--- a/{out_rel_path}")
+        } else {
+            "--- /dev/null".to_string()
+        }
+        .bold(),
+        format!("+++ b/{out_rel_path}").bold()
+    );
+    for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+        s.push_str(&format!("{}\n", hunk.header().to_string().cyan()));
+        for change in hunk.iter_changes() {
+            let line = format!(
+                "{}{}",
+                match change.tag() {
+                    ChangeTag::Delete => "-",
+                    ChangeTag::Insert => "+",
+                    ChangeTag::Equal => " ",
+                },
+                change.value().trim_end_matches(['\r', '\n'])
+            );
+            s.push_str(&match change.tag() {
+                ChangeTag::Delete => line.red().to_string(),
+                ChangeTag::Insert => line.green().to_string(),
+                ChangeTag::Equal => line,
+            });
+            s.push('\n');
+            if change.missing_newline() {
+                s.push_str("\\ No newline at end of file\n");
+            }
+        }
+    }
+    s
+}
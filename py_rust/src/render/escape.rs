@@ -0,0 +1,90 @@
+use std::{collections::BTreeMap, path::Path};
+
+use minijinja::{AutoEscape, Error, Output, State, Value};
+
+use super::matching::Matching;
+use crate::config::engine::Autoescape;
+
+/// The autoescape for a template by its name, from the extension of its output.
+///
+/// So "config.zetch.json", "config.json.zetch" and "config.json.j2" with a match rule are all json, as are includes like "part.json".
+pub fn auto_escape(
+    name: &str,
+    matching: &Matching,
+    autoescape: &BTreeMap<String, Autoescape>,
+) -> AutoEscape {
+    let Some(file_name) = Path::new(name).file_name() else {
+        return AutoEscape::None;
+    };
+    let file_name = file_name.to_string_lossy();
+    let out_name = matching
+        .out_name(&file_name)
+        .unwrap_or_else(|| file_name.to_string());
+    out_escape(&out_name, autoescape)
+}
+
+/// The autoescape for an output by its extension, for when a template's output isn't named after it.
+pub fn out_escape(out_path: &str, autoescape: &BTreeMap<String, Autoescape>) -> AutoEscape {
+    let extension = Path::new(out_path).file_name().and_then(|file_name| {
+        file_name
+            .to_string_lossy()
+            .split('.')
+            .skip(1)
+            .last()
+            .map(|ext| ext.to_string())
+    });
+    match extension.and_then(|ext| autoescape.get(&ext)) {
+        Some(Autoescape::Json) => AutoEscape::Json,
+        Some(mode) => AutoEscape::Custom(mode.name()),
+        None => AutoEscape::None,
+    }
+}
+
+/// Writes values into templates, applying the custom escapes the default formatter doesn't know about.
+pub fn formatter(out: &mut Output, state: &State, value: &Value) -> Result<(), Error> {
+    let AutoEscape::Custom(name) = state.auto_escape() else {
+        return minijinja::escape_formatter(out, state, value);
+    };
+    let rendered = value.to_string();
+    let escaped = if value.is_safe() {
+        rendered
+    } else {
+        match Autoescape::ALL.into_iter().find(|mode| mode.name() == name) {
+            Some(Autoescape::YamlString | Autoescape::TomlString) => escape_string(&rendered)?,
+            Some(Autoescape::Shell) => quote_shell(&rendered),
+            Some(Autoescape::Json) | None => {
+                return Err(Error::new(
+                    minijinja::ErrorKind::InvalidOperation,
+                    format!("Unknown autoescape '{name}'."),
+                ))
+            }
+        }
+    };
+    out.write_str(&escaped).map_err(Error::from)
+}
+
+/// The contents of a double quoted string, json's escapes are all valid in both yaml and toml.
+fn escape_string(value: &str) -> Result<String, Error> {
+    let quoted = serde_json::to_string(value).map_err(|e| {
+        Error::new(
+            minijinja::ErrorKind::BadSerialization,
+            "Failed to escape string.",
+        )
+        .with_source(e)
+    })?;
+    Ok(quoted[1..quoted.len() - 1].to_string())
+}
+
+/// Quote as a single shell word, the same as python's shlex.quote().
+fn quote_shell(value: &str) -> String {
+    if value.is_empty() {
+        return "''".to_string();
+    }
+    if value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "@%+=:,./_-".contains(c))
+    {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', "'\"'\"'"))
+}
@@ -0,0 +1,81 @@
+use std::{collections::HashSet, path::Path};
+
+use super::{front_matter, mini_env::template_env, template::Template};
+use crate::{prelude::*, state::State};
+
+/// Replace each template with a foreach in its front matter with one per item, each rendering to its own output.
+pub fn expand(
+    env: &minijinja::Environment,
+    state: &State,
+    root: &Path,
+    templates: Vec<Template>,
+) -> Result<Vec<Template>, Report<Zerr>> {
+    let mut expanded = Vec::with_capacity(templates.len());
+    for template in templates {
+        let (Some(foreach), Some(out)) =
+            (template.front.foreach.as_ref(), template.front.out.as_ref())
+        else {
+            expanded.push(template);
+            continue;
+        };
+
+        // The output path is rendered with the template's own engine options:
+        let own_env = template_env(env, state, &template)?;
+        let env = own_env.as_ref().unwrap_or(env);
+
+        let items = env
+            .compile_expression(foreach)
+            .and_then(|expr| expr.eval(template.context()))
+            .and_then(|items| items.try_iter().map(|iter| iter.collect::<Vec<_>>()))
+            .map_err(|e| {
+                zerr!(
+                    Zerr::RenderTemplateError,
+                    "Failed to evaluate foreach '{}' in the front matter of '{}', it should be a list.",
+                    foreach,
+                    template.rel_path
+                )
+                .attach_printable(format!("{e}"))
+            })?;
+
+        let mut out_paths = HashSet::new();
+        for item in items {
+            let rendered_out = env
+                .render_str(out, minijinja::context! { item => item, ..template.context() })
+                .map_err(|e| {
+                    zerr!(
+                        Zerr::RenderTemplateError,
+                        "Failed to render the foreach output path '{}' in the front matter of '{}'.",
+                        out,
+                        template.rel_path
+                    )
+                    .attach_printable(format!("{e}"))
+                })?;
+            let out_path = front_matter::resolve_out(
+                root,
+                template.path.parent().unwrap_or(root),
+                &rendered_out,
+            )
+            .attach_printable_lazy(|| {
+                format!(
+                    "From the foreach in the front matter of '{}'.",
+                    template.rel_path
+                )
+            })?;
+            if !out_paths.insert(out_path.clone()) {
+                return Err(zerr!(
+                    Zerr::RenderTemplateError,
+                    "Multiple foreach items in '{}' render to the same output '{}', the out path needs to be unique per item.",
+                    template.rel_path,
+                    rendered_out
+                ));
+            }
+            expanded.push(template.for_item(root, out_path, item));
+        }
+        debug!(
+            "Template '{}' expanded to {} outputs with foreach.",
+            template.rel_path,
+            out_paths.len()
+        );
+    }
+    Ok(expanded)
+}
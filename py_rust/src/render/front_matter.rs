@@ -0,0 +1,207 @@
+use std::path::{Component, Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::{
+    config::{engine::Engine, modes::parse_mode},
+    prelude::*,
+};
+
+/// Lines opening and closing the front matter, which must be the very start of the template.
+///
+/// Toml style like hugo's, so it can't be confused with the "---" starting yaml documents.
+static DELIMITER: &str = "+++";
+
+/// Optional per-template metadata declared in toml at the top of a template, stripped before rendering.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FrontMatter {
+    /// Output path relative to the template's directory, replacing the one from the matcher.
+    ///
+    /// Rendered with each item when used with foreach.
+    pub out: Option<String>,
+    /// An expression evaluating to a list, the template's rendered to a separate output for each item, available as item.
+    pub foreach: Option<String>,
+    /// Extra variables only available to this template, taking precedence over the global context.
+    #[serde(default)]
+    pub context: serde_json::Map<String, serde_json::Value>,
+    /// An expression evaluated with the context, when true the template isn't rendered and has no output.
+    pub skip_if: Option<String>,
+    /// Octal permissions for the output, e.g. "755".
+    #[serde(default, deserialize_with = "deserialize_mode")]
+    pub mode: Option<u32>,
+    /// Whether the template can be skipped when none of its recorded inputs have changed since last rendered.
+    ///
+    /// Disable for templates whose output changes without their inputs changing, e.g. ones using now() or custom functions reading outside files.
+    #[serde(default = "default_cache")]
+    pub cache: bool,
+    /// Engine options overriding the config's [engine] for this template and anything it includes.
+    #[serde(default)]
+    pub engine: serde_json::Map<String, serde_json::Value>,
+    /// How many lines the front matter takes up, to map error line numbers in the body back to the file.
+    #[serde(skip)]
+    pub lines: usize,
+}
+
+fn default_cache() -> bool {
+    true
+}
+
+impl Default for FrontMatter {
+    fn default() -> Self {
+        Self {
+            out: None,
+            foreach: None,
+            context: serde_json::Map::new(),
+            skip_if: None,
+            mode: None,
+            cache: default_cache(),
+            engine: serde_json::Map::new(),
+            lines: 0,
+        }
+    }
+}
+
+impl FrontMatter {
+    /// Read the front matter from the start of a template, the default (empty) when it has none.
+    pub fn load(path: &Path) -> Result<Self, Report<Zerr>> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            // Binary templates can't have front matter:
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData => return Ok(Self::default()),
+            Err(e) => return Err(e).change_context(Zerr::InternalError),
+        };
+        let (front, _) = split(&contents)
+            .attach_printable_lazy(|| format!("Invalid front matter in '{}'.", path.display()))?;
+        let Some((raw, lines)) = front else {
+            return Ok(Self::default());
+        };
+
+        let mut front: Self = toml::from_str(raw)
+            .change_context(Zerr::RenderTemplateError)
+            .attach_printable_lazy(|| format!("Invalid front matter in '{}'.", path.display()))?;
+        front.lines = lines;
+
+        if front.foreach.is_some() && front.out.is_none() {
+            return Err(zerr!(
+                Zerr::RenderTemplateError,
+                "Front matter in '{}' has foreach without out, each item needs its own output path, e.g. out = \"{{{{ item.name }}}}.yaml\".",
+                path.display()
+            ));
+        }
+        Ok(front)
+    }
+
+    /// The output path when overridden, resolved against the template's directory.
+    ///
+    /// None with foreach too, as the path is only known once rendered with each item.
+    pub fn out_path(
+        &self,
+        root: &Path,
+        template_path: &Path,
+    ) -> Result<Option<PathBuf>, Report<Zerr>> {
+        match self.out.as_ref() {
+            Some(out) if self.foreach.is_none() => {
+                resolve_out(root, template_path.parent().unwrap_or(root), out)
+                    .attach_printable_lazy(|| {
+                        format!("From the front matter of '{}'.", template_path.display())
+                    })
+                    .map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// The base engine with this template's overrides applied, None when it has none.
+    pub fn engine(&self, base: &Engine) -> Result<Option<Engine>, Report<Zerr>> {
+        if self.engine.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(
+            base.with_options(&self.engine)
+                .change_context(Zerr::RenderTemplateError)?,
+        ))
+    }
+}
+
+/// Resolve an output path against a directory, it has to be a file inside the root.
+pub fn resolve_out(root: &Path, dir: &Path, out: &str) -> Result<PathBuf, Report<Zerr>> {
+    let abs_root = resolve(root)?;
+    let resolved = resolve(&dir.join(out))?;
+    match resolved.strip_prefix(&abs_root) {
+        Ok(rel) if rel.components().next().is_some() => Ok(root.join(rel)),
+        _ => Err(zerr!(
+            Zerr::RenderTemplateError,
+            "Output path '{}' isn't a file inside the root.",
+            out
+        )),
+    }
+}
+
+/// The template with any front matter removed, what's actually rendered.
+pub fn body(contents: &str) -> &str {
+    match split(contents) {
+        Ok((_, body)) => body,
+        // Unclosed front matter fails when the template's found, the raw contents are fine here:
+        Err(_) => contents,
+    }
+}
+
+/// Split the raw front matter and its line count from the body, erroring if it's never closed.
+fn split(contents: &str) -> Result<(Option<(&str, usize)>, &str), Report<Zerr>> {
+    let mut lines = contents.split_inclusive('\n');
+    if lines.next().map(|line| line.trim_end_matches(['\r', '\n'])) != Some(DELIMITER) {
+        return Ok((None, contents));
+    }
+
+    let start = contents.find('\n').map_or(contents.len(), |idx| idx + 1);
+    let mut offset = start;
+    for (idx, line) in lines.enumerate() {
+        let line_start = offset;
+        offset += line.len();
+        if line.trim_end_matches(['\r', '\n']) == DELIMITER {
+            // The opening and closing delimiters plus everything between:
+            return Ok((
+                Some((&contents[start..line_start], idx + 2)),
+                &contents[offset..],
+            ));
+        }
+    }
+    Err(zerr!(
+        Zerr::RenderTemplateError,
+        "Front matter opened with '{}' on the first line is never closed.",
+        DELIMITER
+    ))
+}
+
+/// Make absolute and resolve '..' lexically, as the output may not exist yet.
+fn resolve(path: &Path) -> Result<PathBuf, Report<Zerr>> {
+    let mut resolved = PathBuf::new();
+    for component in std::path::absolute(path)
+        .change_context(Zerr::InternalError)?
+        .components()
+    {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            other => resolved.push(other),
+        }
+    }
+    Ok(resolved)
+}
+
+/// Modes are written as octal strings, as a toml integer like 755 would be taken as decimal.
+fn deserialize_mode<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u32>, D::Error> {
+    let Some(mode) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    parse_mode(&mode).map(Some).ok_or_else(|| {
+        serde::de::Error::custom(format!(
+            "invalid mode '{mode}', expected octal permissions like \"755\""
+        ))
+    })
+}
@@ -0,0 +1,94 @@
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use super::front_matter;
+use crate::state::State;
+
+/// Where the names used in include/import/extends are looked up: the root, then each of the config's template_dirs in order.
+#[derive(Debug, Clone)]
+pub struct SearchPaths {
+    dirs: Vec<PathBuf>,
+    /// Whether ./ and ../ names are relative to the including template, engine.relative_includes.
+    relative: bool,
+}
+
+impl SearchPaths {
+    pub fn new(root: &Path, state: &State) -> Self {
+        Self {
+            dirs: std::iter::once(root.to_path_buf())
+                .chain(state.conf.engine.template_dirs.iter().map(PathBuf::from))
+                .collect(),
+            relative: state.conf.engine.relative_includes,
+        }
+    }
+
+    /// The file a template name refers to, None when it can't be found.
+    ///
+    /// Absolute names are used as is, others are tried against each search path in turn.
+    pub fn resolve(&self, name: &str) -> Option<PathBuf> {
+        let path = Path::new(name);
+        if path.is_absolute() {
+            return path.is_file().then(|| path.to_path_buf());
+        }
+        self.dirs
+            .iter()
+            .map(|dir| dir.join(path))
+            .find(|candidate| candidate.is_file())
+    }
+
+    /// The name to load when the parent template pulls in another, used as the env's path join callback.
+    ///
+    /// With engine.relative_includes, names starting with ./ or ../ are relative to the parent when that exists.
+    /// Otherwise they're left as written, where ./ means the root.
+    pub fn join(&self, name: &str, parent: &str) -> String {
+        if !self.relative || !(name.starts_with("./") || name.starts_with("../")) {
+            return name.to_string();
+        }
+        let Some(parent_dir) = Path::new(parent).parent() else {
+            return name.to_string();
+        };
+        let joined = normalize(&parent_dir.join(name));
+        if self.resolve(&joined).is_some() {
+            joined
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// The source of a template by name, with any front matter stripped as it's metadata, never rendered.
+    pub fn load(&self, name: &str) -> Result<Option<String>, minijinja::Error> {
+        let Some(path) = self.resolve(name) else {
+            return Ok(None);
+        };
+        match fs::read_to_string(path) {
+            Ok(result) => Ok(Some(front_matter::body(&result).to_string())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(minijinja::Error::new(
+                minijinja::ErrorKind::InvalidOperation,
+                "could not read template",
+            )
+            .with_source(err)),
+        }
+    }
+}
+
+/// Lexically remove . and .. from a joined name, keeping leading .. that can't be removed.
+fn normalize(path: &Path) -> String {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if normalized.file_name().is_some() {
+                    normalized.pop();
+                } else {
+                    normalized.push("..");
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized.to_string_lossy().to_string()
+}
@@ -7,8 +7,8 @@ use std::{
 use sha2::{digest::generic_array::GenericArray, Digest, Sha256};
 use tracing::{debug, warn};
 
-use super::template;
-use crate::prelude::*;
+use super::{deps::Deps, regions, staging::Staging, template};
+use crate::{config::conf::Orphans, prelude::*};
 pub static LOCKFILE_NAME: &str = ".zetch.lock";
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -17,6 +17,47 @@ struct Contents {
     // Keep ordering in lockfile static to reduce git conflicts and diff noise:
     #[serde(serialize_with = "crate::utils::ordered_map_serializer")]
     files: HashMap<String, String>,
+    // The inputs each template was rendered with, to allow skipping unchanged templates:
+    #[serde(default, serialize_with = "crate::utils::ordered_map_serializer")]
+    deps: HashMap<String, Deps>,
+    // Where each template was rendered to, relative to the root, to allow cleaning up outputs of removed templates:
+    #[serde(default, serialize_with = "crate::utils::ordered_map_serializer")]
+    outputs: HashMap<String, String>,
+    // Hashes of the rendered bodies of each templated region in ordinary files, to protect regions edited by hand:
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "crate::utils::ordered_map_serializer"
+    )]
+    regions: HashMap<String, Vec<String>>,
+    // The octal permissions each output was last given, changes to which need the output updating:
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "crate::utils::ordered_map_serializer"
+    )]
+    modes: HashMap<String, String>,
+    // Hashes of outputs after post tasks changed them (e.g. formatters), so those changes aren't mistaken for edits by hand:
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "crate::utils::ordered_map_serializer"
+    )]
+    formatted: HashMap<String, String>,
+    // Orphaned outputs left in place with orphans = "warn", by output path, so they're warned about until resolved:
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "crate::utils::ordered_map_serializer"
+    )]
+    orphans: HashMap<String, Orphan>,
+}
+
+/// An output whose template no longer renders to it.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct Orphan {
+    template: String,
+    hashes: Vec<String>,
 }
 
 impl Contents {
@@ -24,13 +65,19 @@ impl Contents {
         Self {
             version: env!("CARGO_PKG_VERSION").to_string(),
             files: HashMap::new(),
+            deps: HashMap::new(),
+            outputs: HashMap::new(),
+            regions: HashMap::new(),
+            modes: HashMap::new(),
+            formatted: HashMap::new(),
+            orphans: HashMap::new(),
         }
     }
 }
 
-pub fn hash_contents(contents: &str) -> String {
+pub fn hash_contents(contents: impl AsRef<[u8]>) -> String {
     let mut hasher = Sha256::new();
-    hasher.update(contents.as_bytes());
+    hasher.update(contents.as_ref());
     let mut out = GenericArray::default();
     hasher.finalize_into_reset(&mut out);
     format!("{out:x}")
@@ -39,7 +86,14 @@ pub fn hash_contents(contents: &str) -> String {
 pub struct Lockfile {
     filepath: PathBuf,
     seen_template_paths: HashSet<String>,
+    seen_out_paths: HashSet<String>,
+    // Previous outputs of templates now rendering elsewhere, by template key, with their last hashes:
+    moved_outputs: Vec<(String, String, Vec<String>)>,
+    // Outputs written this run, by template key, checked for changes made by post tasks:
+    written: Vec<(String, PathBuf)>,
     contents: Contents,
+    // Outputs waiting to be moved into place:
+    staging: Staging,
     // Modified at the moment is the same as newly_created,
     // but during template additions modified may become different:
     pub _newly_created: bool,
@@ -117,43 +171,242 @@ impl Lockfile {
             filepath,
             contents,
             seen_template_paths: HashSet::new(),
+            seen_out_paths: HashSet::new(),
+            moved_outputs: vec![],
+            written: vec![],
+            staging: Staging::default(),
             _newly_created: newly_created,
             modified,
         }
     }
 
+    /// Move the outputs of all added templates into place, called once every template has been added successfully.
+    pub fn commit_outputs(&mut self) -> Result<(), Report<Zerr>> {
+        self.staging.commit()
+    }
+
+    /// Throw away the outputs of added templates after a failure, leaving the previous ones in place.
+    pub fn discard_outputs(&mut self) {
+        self.staging.discard();
+    }
+
+    /// Record the outputs written this run that post tasks have since changed, e.g. formatted,
+    /// so the changes aren't treated as edits by hand next time.
+    ///
+    /// Post tasks run after the lockfile's synced, so it's saved again when there are any.
+    pub fn record_post_task_changes(&mut self) -> Result<(), Report<Zerr>> {
+        let mut recorded = false;
+        for (key, out_path) in std::mem::take(&mut self.written) {
+            let contents = match fs::read(&out_path) {
+                Ok(contents) => contents,
+                // Removed by a post task, nothing to record:
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err).change_context(Zerr::InternalError),
+            };
+            let hashed = hash_contents(&contents);
+            if self.contents.files.get(&key) != Some(&hashed) {
+                debug!(
+                    "Output of template '{}' changed by post tasks, recording its new hash.",
+                    key
+                );
+                self.modified = true;
+                recorded = true;
+                self.contents.formatted.insert(key, hashed);
+            }
+        }
+        if recorded {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// The hashes the template's output could have on disk when unchanged since last rendered, as written and after post tasks.
+    fn last_hashes(&self, key: &str) -> Vec<&String> {
+        [
+            self.contents.files.get(key),
+            self.contents.formatted.get(key),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// The inputs the template was last rendered with, if known.
+    pub fn deps(&self, template: &template::Template) -> Option<&Deps> {
+        self.contents.deps.get(&template.key)
+    }
+
+    /// Whether the output was last given these permissions and still has them.
+    pub fn mode_unchanged(&self, template: &template::Template, mode: Option<u32>) -> bool {
+        mode.is_none_or(|mode| {
+            self.contents.modes.get(&template.key) == Some(&format!("{mode:o}"))
+                && file_mode(&template.out_path)
+                    .is_ok_and(|existing| existing.is_none_or(|existing| existing == mode))
+        })
+    }
+
+    /// Whether the output on disk still matches what was last rendered, skipping the template would otherwise hide changes made to it.
+    pub fn output_unchanged(&self, template: &template::Template) -> bool {
+        if template.regions {
+            let Ok(existing) = fs::read_to_string(&template.out_path) else {
+                return false;
+            };
+            return regions::parse(&existing).is_ok_and(|parsed| {
+                self.contents
+                    .regions
+                    .get(&template.key)
+                    .is_some_and(|recorded| {
+                        recorded.len() == parsed.len()
+                            && parsed.iter().zip(recorded.iter()).all(|(region, hash)| {
+                                &hash_contents(&existing[region.body.clone()]) == hash
+                            })
+                    })
+            });
+        }
+        let Ok(existing) = fs::read(&template.out_path) else {
+            return false;
+        };
+        self.last_hashes(&template.key)
+            .contains(&&hash_contents(&existing))
+    }
+
+    /// Check the template's existing output on disk can be safely overwritten with the newly compiled contents.
+    ///
+    /// Returns the reason when it can't: it exists but wasn't written by zetch, or it's been modified since last rendered.
+    pub fn overwrite_conflict(
+        &self,
+        template: &template::Template,
+        compiled: &[u8],
+    ) -> Result<Option<&'static str>, Report<Zerr>> {
+        if template.regions {
+            return self.region_conflict(
+                template,
+                std::str::from_utf8(compiled).change_context(Zerr::InternalError)?,
+            );
+        }
+
+        let existing = match fs::read(&template.out_path) {
+            Ok(existing) => existing,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).change_context(Zerr::InternalError),
+        };
+
+        // Nothing would be lost:
+        if existing == compiled {
+            return Ok(None);
+        }
+
+        // The output may have been last written by a different (e.g. since renamed) template:
+        let key = if self.contents.files.contains_key(&template.key) {
+            Some(&template.key)
+        } else {
+            self.contents
+                .outputs
+                .iter()
+                .find(|(_, out_path)| *out_path == &template.out_rel_path)
+                .map(|(template_path, _)| template_path)
+        };
+
+        Ok(match key.map(|key| self.last_hashes(key)) {
+            None => Some("exists but wasn't written by zetch"),
+            Some(last_hashes) if !last_hashes.contains(&&hash_contents(&existing)) => {
+                Some("has been modified since last rendered")
+            }
+            Some(_) => None,
+        })
+    }
+
+    /// Files with templated regions are hand written, so only the region bodies need protecting.
+    fn region_conflict(
+        &self,
+        template: &template::Template,
+        compiled: &str,
+    ) -> Result<Option<&'static str>, Report<Zerr>> {
+        let Some(recorded) = self.contents.regions.get(&template.key) else {
+            return Ok(None);
+        };
+        let existing = match fs::read_to_string(&template.out_path) {
+            Ok(existing) => existing,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).change_context(Zerr::InternalError),
+        };
+
+        let existing_regions = regions::parse(&existing)?;
+        let compiled_regions = regions::parse(compiled)?;
+        for ((existing_region, compiled_region), last_hash) in existing_regions
+            .iter()
+            .zip(compiled_regions.iter())
+            .zip(recorded.iter())
+        {
+            let body = &existing[existing_region.body.clone()];
+            if body != &compiled[compiled_region.body.clone()] && &hash_contents(body) != last_hash
+            {
+                return Ok(Some(
+                    "has a templated region that's been modified since last rendered",
+                ));
+            }
+        }
+        Ok(None)
+    }
+
     /// After compiling a template run this, it will update the lockfile and write the compiled template to disk.
     ///
     /// Returns true when added, false when identical already present in lockfile.
     pub fn add_template(
         &mut self,
         template: &template::Template,
-        compiled: String,
+        compiled: Vec<u8>,
+        deps: Deps,
+        mode: Option<u32>,
     ) -> Result<bool, Report<Zerr>> {
         // To prevent bloating the filesize and readability of the lockfile, only include a hash of the compiled template rather than the full contents. (sha-256)
         let hashed = timeit!("Hashing compiled files for lockfile", {
             hash_contents(&compiled)
         });
 
-        let identical = if let Some(old_hashed) = self.contents.files.get(&template.rel_path) {
+        // The output has moved, e.g. a variable in its path changed, so the old one is orphaned:
+        let moved_from = self
+            .contents
+            .outputs
+            .get(&template.key)
+            .filter(|old_out| *old_out != &template.out_rel_path);
+        if let Some(old_out) = moved_from {
+            self.moved_outputs.push((
+                template.key.clone(),
+                old_out.clone(),
+                self.last_hashes(&template.key)
+                    .into_iter()
+                    .cloned()
+                    .collect(),
+            ));
+        }
+
+        let identical = if moved_from.is_some() {
+            debug!(
+                "Template '{}' output has moved, updating lockfile and rewriting.",
+                template.key
+            );
+            self.modified = true;
+            false
+        } else if let Some(old_hashed) = self.contents.files.get(&template.key) {
             if old_hashed != &hashed {
                 debug!(
                     "Template '{}' has changed, updating lockfile and rewriting.",
-                    template.rel_path
+                    template.key
                 );
                 self.modified = true;
                 false
             } else {
                 debug!(
                     "Template '{}' has identical hash in lockfile, skipping.",
-                    template.rel_path
+                    template.key
                 );
                 true
             }
         } else {
             debug!(
                 "Template '{}' didn't exist in lockfile prior, updating lockfile and rewriting.",
-                template.rel_path
+                template.key
             );
             self.modified = true;
             false
@@ -161,27 +414,118 @@ impl Lockfile {
 
         // Only update if not already identical:
         if !identical {
+            self.modified = true;
+            self.contents.files.insert(template.key.clone(), hashed);
+            // Post tasks may change the new output differently, they're checked again once run:
+            self.contents.formatted.remove(&template.key);
+            if !template.regions {
+                self.written
+                    .push((template.key.clone(), template.out_path.clone()));
+            }
+
+            // Only moved into place once everything else has succeeded:
+            self.staging.write(&template.out_path, &compiled, mode)?;
+        }
+
+        // A change in permissions is a change to the output too, including ones made by hand which are put back:
+        let mut mode_changed = false;
+        if let Some(mode) = mode.filter(|_| !identical || template.out_path.exists()) {
+            if identical && file_mode(&template.out_path)?.is_some_and(|existing| existing != mode)
+            {
+                self.staging.set_mode(&template.out_path, mode);
+                mode_changed = true;
+            }
+            let mode = format!("{mode:o}");
+            if self.contents.modes.get(&template.key) != Some(&mode) {
+                self.modified = true;
+                self.contents.modes.insert(template.key.clone(), mode);
+            }
+        }
+
+        if self.contents.deps.get(&template.key) != Some(&deps) {
+            self.modified = true;
+            self.contents.deps.insert(template.key.clone(), deps);
+        }
+
+        if template.regions {
+            let compiled = std::str::from_utf8(&compiled).change_context(Zerr::InternalError)?;
+            let hashes = regions::parse(compiled)?
+                .iter()
+                .map(|region| hash_contents(&compiled[region.body.clone()]))
+                .collect::<Vec<_>>();
+            if self.contents.regions.get(&template.key) != Some(&hashes) {
+                self.modified = true;
+                self.contents.regions.insert(template.key.clone(), hashes);
+            }
+        // The output is the file itself, which mustn't be cleaned up as an orphan:
+        } else if self.contents.outputs.get(&template.key) != Some(&template.out_rel_path) {
             self.modified = true;
             self.contents
-                .files
-                .insert(template.rel_path.clone(), hashed);
+                .outputs
+                .insert(template.key.clone(), template.out_rel_path.clone());
+        }
 
-            // Write the compiled file:
-            fs::write(template.out_path.clone(), compiled).change_context(Zerr::InternalError)?;
+        self.keep_template(template);
+
+        Ok(!identical || mode_changed)
+    }
+
+    /// Mark a template as still existing without re-rendering it, so its entry survives sync().
+    pub fn keep_template(&mut self, template: &template::Template) {
+        self.seen_template_paths.insert(template.key.clone());
+        self.seen_out_paths.insert(template.out_rel_path.clone());
+
+        // Before a foreach is expanded its items are unknown, so keep the entries of all of them:
+        if template.front.foreach.is_some() && template.item.is_none() {
+            self.keep_where(|template_path| template_path == template.rel_path);
         }
+    }
 
-        self.seen_template_paths.insert(template.rel_path.clone());
+    /// Mark every entry whose template isn't selected as still existing, so rendering a selection doesn't prune the rest in sync().
+    pub fn keep_unselected(&mut self, is_selected: impl Fn(&str) -> bool) {
+        self.keep_where(|template_path| !is_selected(template_path));
+    }
 
-        Ok(!identical)
+    /// Mark the entries of templates matching the predicate as still existing, given template paths relative to the root.
+    fn keep_where(&mut self, keep: impl Fn(&str) -> bool) {
+        for template_path in self.contents.files.keys() {
+            if !keep(template::key_template_path(template_path)) {
+                continue;
+            }
+            self.seen_template_paths.insert(template_path.clone());
+            if let Some(out_path) = self.contents.outputs.get(template_path) {
+                self.seen_out_paths.insert(out_path.clone());
+            }
+        }
     }
 
     /// After all compiled templates have been added, run this to close out and save the lockfile.
-    pub fn sync(&mut self) -> Result<(), Report<Zerr>> {
+    ///
+    /// Outputs of templates that no longer exist are handled according to the orphans config,
+    /// returns the outputs that were deleted, relative to the root.
+    pub fn sync(&mut self, orphans: Orphans) -> Result<Vec<String>, Report<Zerr>> {
+        let deleted = self.handle_orphans(orphans)?;
+
         let before_len = self.contents.files.len();
         // Anything which isn't in the new compiled set should be removed from the lockfile:
         self.contents
             .files
             .retain(|template_path, _| self.seen_template_paths.contains(template_path));
+        self.contents
+            .deps
+            .retain(|template_path, _| self.seen_template_paths.contains(template_path));
+        self.contents
+            .outputs
+            .retain(|template_path, _| self.seen_template_paths.contains(template_path));
+        self.contents
+            .regions
+            .retain(|template_path, _| self.seen_template_paths.contains(template_path));
+        self.contents
+            .modes
+            .retain(|template_path, _| self.seen_template_paths.contains(template_path));
+        self.contents
+            .formatted
+            .retain(|template_path, _| self.seen_template_paths.contains(template_path));
 
         if self.contents.files.len() != before_len {
             debug!(
@@ -192,15 +536,169 @@ impl Lockfile {
         }
 
         if self.modified {
-            // Write the updated lockfile
-            debug!("Writing updated lockfile to '{}'", self.filepath.display());
-            fs::write(
-                &self.filepath,
-                serde_json::to_string_pretty(&self.contents).change_context(Zerr::InternalError)?,
+            self.save()?;
+        }
+
+        Ok(deleted)
+    }
+
+    /// Write the lockfile, replacing the old one in one go so it's never left half written.
+    fn save(&self) -> Result<(), Report<Zerr>> {
+        debug!("Writing updated lockfile to '{}'", self.filepath.display());
+        let mut staging = Staging::default();
+        staging.write(
+            &self.filepath,
+            serde_json::to_string_pretty(&self.contents)
+                .change_context(Zerr::InternalError)?
+                .as_bytes(),
+            None,
+        )?;
+        staging.commit()
+    }
+
+    /// Delete or warn about outputs whose templates no longer exist.
+    ///
+    /// Outputs that have been modified since last rendered are never deleted, and neither are outputs now rendered by another template (e.g. a renamed template).
+    /// Those only warned about stay tracked, so are warned about again until deleted or rendered to again.
+    fn handle_orphans(&mut self, orphans: Orphans) -> Result<Vec<String>, Report<Zerr>> {
+        let mut deleted = vec![];
+        let mut kept = HashMap::new();
+        if orphans == Orphans::Keep {
+            self.keep_orphans(kept);
+            return Ok(deleted);
+        }
+
+        let root = self
+            .filepath
+            .parent()
+            .ok_or_else(|| {
+                zerr!(
+                    Zerr::InternalError,
+                    "Lockfile path has no parent: '{}'",
+                    self.filepath.display()
+                )
+            })?
+            .to_path_buf();
+
+        let mut orphaned = self
+            .contents
+            .outputs
+            .iter()
+            .filter(|(template_path, _)| !self.seen_template_paths.contains(*template_path))
+            .map(|(template_path, out_path)| {
+                (
+                    template_path.clone(),
+                    out_path.clone(),
+                    self.last_hashes(template_path)
+                        .into_iter()
+                        .cloned()
+                        .collect::<Vec<_>>(),
+                    "no longer exists",
+                )
+            })
+            .chain(
+                self.moved_outputs
+                    .iter()
+                    .map(|(template_path, out_path, last_hashes)| {
+                        (
+                            template_path.clone(),
+                            out_path.clone(),
+                            last_hashes.clone(),
+                            "now renders elsewhere",
+                        )
+                    }),
             )
-            .change_context(Zerr::InternalError)?;
+            .chain(self.contents.orphans.iter().map(|(out_path, orphan)| {
+                (
+                    orphan.template.clone(),
+                    out_path.clone(),
+                    orphan.hashes.clone(),
+                    "no longer renders to it",
+                )
+            }))
+            .filter(|(_, out_path, ..)| !self.seen_out_paths.contains(out_path))
+            .collect::<Vec<_>>();
+        orphaned.sort();
+        // An output is only handled once, however it was orphaned:
+        let mut handled = HashSet::new();
+        orphaned.retain(|(_, out_path, ..)| handled.insert(out_path.clone()));
+
+        for (template_path, out_path, last_hashes, reason) in orphaned {
+            let path = root.join(&out_path);
+            let contents = match fs::read(&path) {
+                Ok(contents) => contents,
+                // Already gone:
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err).change_context(Zerr::InternalError),
+            };
+
+            if !last_hashes.contains(&hash_contents(&contents)) {
+                warn!(
+                    "Template '{}' {}, but its output '{}' has been modified since last rendered so won't be deleted.",
+                    template_path, reason, out_path
+                );
+            } else if orphans == Orphans::Delete {
+                debug!(
+                    "Template '{}' {}, deleting its output '{}'.",
+                    template_path, reason, out_path
+                );
+                fs::remove_file(&path).change_context(Zerr::InternalError)?;
+                remove_empty_dirs(&root, &path);
+                deleted.push(out_path);
+                continue;
+            } else {
+                warn!(
+                    "Template '{}' {}, its output '{}' is orphaned. Set orphans = \"delete\" in the config to remove it automatically.",
+                    template_path, reason, out_path
+                );
+            }
+            if orphans == Orphans::Warn {
+                kept.insert(
+                    out_path,
+                    Orphan {
+                        template: template_path,
+                        hashes: last_hashes,
+                    },
+                );
+            }
         }
+        self.keep_orphans(kept);
 
-        Ok(())
+        Ok(deleted)
+    }
+
+    fn keep_orphans(&mut self, kept: HashMap<String, Orphan>) {
+        if kept != self.contents.orphans {
+            self.contents.orphans = kept;
+            self.modified = true;
+        }
+    }
+}
+
+/// Remove the directories above a deleted output that it leaves empty, stopping at the root.
+fn remove_empty_dirs(root: &std::path::Path, path: &std::path::Path) {
+    for dir in path.ancestors().skip(1) {
+        if dir == root || !dir.starts_with(root) || fs::remove_dir(dir).is_err() {
+            break;
+        }
     }
 }
+
+/// The permissions of a file, None on platforms without unix modes.
+#[cfg(unix)]
+pub fn file_mode(path: &std::path::Path) -> Result<Option<u32>, Report<Zerr>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    Ok(Some(
+        fs::metadata(path)
+            .change_context(Zerr::InternalError)?
+            .permissions()
+            .mode()
+            & 0o7777,
+    ))
+}
+
+#[cfg(not(unix))]
+pub fn file_mode(_path: &std::path::Path) -> Result<Option<u32>, Report<Zerr>> {
+    Ok(None)
+}
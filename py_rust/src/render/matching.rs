@@ -0,0 +1,218 @@
+use regex::Regex;
+
+use crate::{config::conf::Config, prelude::*};
+
+/// Identifies templates by their file or directory names, and names their outputs.
+///
+/// Built from the config's matchers, then its match_rules, the first to give an output name is used.
+#[derive(Clone, Debug)]
+pub struct Matching {
+    rules: Vec<Rule>,
+}
+
+/// A single way of identifying templates.
+#[derive(Clone, Debug)]
+enum Rule {
+    /// E.g. "zetch" matching "foo.zetch.txt" and "foo.txt.zetch", the matcher removed in the output.
+    Matcher {
+        matcher: String,
+        middle_regex: Regex,
+        end_regex: Regex,
+    },
+    /// From match_rules, e.g. "*.j2" matching "foo.txt.j2", the output named by putting what "*" matched into out.
+    Pattern {
+        regex: Regex,
+        out: String,
+        pattern: String,
+    },
+}
+
+impl Matching {
+    pub fn new(conf: &Config) -> Self {
+        Self {
+            rules: conf
+                .matchers
+                .iter()
+                .map(|matcher| Rule::matcher(matcher))
+                .chain(
+                    conf.match_rules
+                        .iter()
+                        .map(|rule| Rule::pattern(&rule.pattern, &rule.out)),
+                )
+                .collect(),
+        }
+    }
+
+    /// Just a matcher, or the pattern of one of the config's match_rules when it contains a "*".
+    ///
+    /// Used by the replace-matcher command.
+    pub fn single(conf: &Config, matcher_or_pattern: &str) -> Result<Self, Report<Zerr>> {
+        let rule = if matcher_or_pattern.contains('*') {
+            let match_rule = conf
+                .match_rules
+                .iter()
+                .find(|rule| rule.pattern == matcher_or_pattern)
+                .ok_or_else(|| {
+                    zerr!(
+                        Zerr::ConfigInvalid,
+                        "'{}' isn't the pattern of one of the config's match_rules, they need adding there first so the naming of their outputs is known.",
+                        matcher_or_pattern
+                    )
+                })?;
+            Rule::pattern(&match_rule.pattern, &match_rule.out)
+        } else {
+            Rule::matcher(matcher_or_pattern)
+        };
+        Ok(Self { rules: vec![rule] })
+    }
+
+    /// The output name for a file or directory name, None when it isn't a template.
+    pub fn out_name(&self, name: &str) -> Option<String> {
+        self.rules
+            .iter()
+            .find_map(|rule| rule.out_name(name))
+            // E.g. ".zetch" would have nothing left:
+            .filter(|out_name| !out_name.is_empty())
+    }
+
+    /// The new name of a template for it to be matched by the other's rules instead, None when this doesn't match it.
+    ///
+    /// Matchers are swapped in place, otherwise the name is whatever the other would give the same output.
+    pub fn rename(&self, name: &str, other: &Matching) -> Result<Option<String>, Report<Zerr>> {
+        let Some(out_name) = self.out_name(name) else {
+            return Ok(None);
+        };
+
+        let (Some(rule), Some(other_rule)) = (self.rules.first(), other.rules.first()) else {
+            return Ok(None);
+        };
+        let new_name = match (rule, other_rule) {
+            (
+                Rule::Matcher {
+                    middle_regex,
+                    end_regex,
+                    ..
+                },
+                Rule::Matcher { matcher, .. },
+            ) => Some(rewrite_template_matcher(
+                name,
+                middle_regex,
+                end_regex,
+                matcher,
+            )),
+            _ => other_rule.template_name(&out_name),
+        };
+        match new_name.filter(|new_name| other.out_name(new_name).as_ref() == Some(&out_name)) {
+            Some(new_name) => Ok(Some(new_name)),
+            None => Err(zerr!(
+                Zerr::ConfigInvalid,
+                "Can't rename '{}', '{}' can't name a template rendering to '{}'.",
+                name,
+                other_rule.name(),
+                out_name
+            )),
+        }
+    }
+}
+
+impl Rule {
+    fn matcher(matcher: &str) -> Self {
+        Self::Matcher {
+            matcher: matcher.to_string(),
+            middle_regex: Regex::new(&format!(r"(.*)(\.{matcher}\.)(.*)"))
+                .expect("Regex failed to compile"),
+            end_regex: Regex::new(&format!(r"(.*)(\.{matcher})$"))
+                .expect("Regex failed to compile"),
+        }
+    }
+
+    fn pattern(pattern: &str, out: &str) -> Self {
+        let (prefix, suffix) = pattern.split_once('*').unwrap_or((pattern, ""));
+        Self::Pattern {
+            regex: Regex::new(&format!(
+                r"^{}(.+){}$",
+                regex::escape(prefix),
+                regex::escape(suffix)
+            ))
+            .expect("Regex failed to compile"),
+            out: out.to_string(),
+            pattern: pattern.to_string(),
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Self::Matcher { matcher, .. } => matcher,
+            Self::Pattern { pattern, .. } => pattern,
+        }
+    }
+
+    fn out_name(&self, name: &str) -> Option<String> {
+        match self {
+            Self::Matcher {
+                middle_regex,
+                end_regex,
+                ..
+            } => {
+                if let Some(caps) = middle_regex.captures(name) {
+                    return Some(format!(
+                        "{}.{}",
+                        caps.get(1).map_or("", |m| m.as_str()),
+                        caps.get(3).map_or("", |m| m.as_str())
+                    ));
+                }
+                end_regex
+                    .captures(name)
+                    .map(|caps| caps.get(1).map_or("", |m| m.as_str()).to_string())
+            }
+            Self::Pattern { regex, out, .. } => regex
+                .captures(name)
+                .and_then(|caps| caps.get(1))
+                .map(|stem| out.replacen('*', stem.as_str(), 1)),
+        }
+    }
+
+    /// The name of a template rendering to the given output name, None when the rule can't give that name.
+    fn template_name(&self, out_name: &str) -> Option<String> {
+        match self {
+            // Before the extension like "foo.zetch.txt", at the end when there isn't one:
+            Self::Matcher { matcher, .. } => Some(match out_name.rsplit_once('.') {
+                Some((stem, ext)) if !stem.is_empty() => format!("{stem}.{matcher}.{ext}"),
+                _ => format!("{out_name}.{matcher}"),
+            }),
+            Self::Pattern { out, pattern, .. } => {
+                let (prefix, suffix) = out.split_once('*').unwrap_or((out, ""));
+                out_name
+                    .strip_prefix(prefix)
+                    .and_then(|rest| rest.strip_suffix(suffix))
+                    .filter(|stem| !stem.is_empty())
+                    .map(|stem| pattern.replacen('*', stem, 1))
+            }
+        }
+    }
+}
+
+/// Replace the matcher in the filename with the new matcher.
+fn rewrite_template_matcher(
+    filename: &str,
+    middle_regex: &Regex,
+    end_regex: &Regex,
+    new_matcher: &str,
+) -> String {
+    let filename = if let Some(caps) = middle_regex.captures(filename) {
+        format!(
+            "{}.{}.{}",
+            caps.get(1).map_or("", |m| m.as_str()),
+            new_matcher,
+            caps.get(3).map_or("", |m| m.as_str())
+        )
+    } else {
+        filename.to_string()
+    };
+
+    if let Some(caps) = end_regex.captures(&filename) {
+        format!("{}.{}", caps.get(1).map_or("", |m| m.as_str()), new_matcher)
+    } else {
+        filename
+    }
+}
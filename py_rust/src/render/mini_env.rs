@@ -1,56 +1,58 @@
-use std::{collections::HashMap, fs, io, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
 
-use minijinja::syntax::SyntaxConfig;
+use minijinja::{syntax::SyntaxConfig, AutoEscape};
 use pyo3::prelude::*;
 use pythonize::depythonize;
 
-use crate::{custom_exts::py_interface, prelude::*, state::State};
+use super::{
+    config_globs, deps::Tracker, escape, loader::SearchPaths, matching::Matching,
+    preload::add_preloads, template::Template,
+};
+use crate::{config::engine::Engine, custom_exts::py_interface, prelude::*, state::State};
 
-pub fn new_mini_env<'a>(
+pub fn new_mini_env(
     root: &Path,
-    state: &'a State,
-) -> Result<minijinja::Environment<'a>, Report<Zerr>> {
-    let mut env: minijinja::Environment<'a> = minijinja::Environment::new();
+    state: &State,
+    tracker: &mut Tracker,
+) -> Result<minijinja::Environment<'static>, Report<Zerr>> {
+    let mut env = minijinja::Environment::new();
     // Adding in extra builtins like urlencode, tojson and pluralize:
     minijinja_contrib::add_to_environment(&mut env);
 
     // User configurable options added below:
 
-    env.set_syntax(
-        SyntaxConfig::builder()
-            .block_delimiters(
-                state.conf.engine.block_start.clone(),
-                state.conf.engine.block_end.clone(),
-            )
-            .variable_delimiters(
-                state.conf.engine.variable_start.clone(),
-                state.conf.engine.variable_end.clone(),
-            )
-            .comment_delimiters(
-                state.conf.engine.comment_start.clone(),
-                state.conf.engine.comment_end.clone(),
-            )
-            .build()
-            .change_context(Zerr::InternalError)?,
-    );
+    set_options(
+        &mut env,
+        &state.conf.engine,
+        &Matching::new(&state.conf),
+        None,
+    )?;
 
     // Used to be user configurable, but want to modify code as little as possible, so forcibly disable modification of newlines:
     env.set_keep_trailing_newline(true);
 
-    // Forcibly prevent undefined vars:
-    env.set_undefined_behavior(minijinja::UndefinedBehavior::Strict);
+    // Handles the custom escapes opted into with engine.autoescape:
+    env.set_formatter(escape::formatter);
 
-    // Disable all default auto escaping, this caused problems with e.g. adding strings around values in json files:
-    env.set_auto_escape_callback(|_: &str| -> minijinja::AutoEscape {
-        minijinja::AutoEscape::None
-    });
+    // This will allow loading files from templates using the relative root e.g. ./template where . is the root dir, falling back to the config's template_dirs:
+    let search = SearchPaths::new(root, state);
+    let loader = search.clone();
+    env.set_loader(move |name| loader.load(name));
 
-    // This will allow loading files from templates using the relative root e.g. ./template where . is the root dir:
-    env.set_loader(custom_loader(root));
+    // Record every include/import/extends so changes to them can be detected in later renders:
+    let recorder = tracker.clone();
+    env.set_path_join_callback(move |name, parent| {
+        let joined = search.join(name, parent);
+        recorder.record_include(&joined, parent);
+        joined.into()
+    });
 
     // Load in the context:
     for (name, value) in state.ctx.iter() {
-        env.add_global(name, minijinja::Value::from_serialize(value));
+        env.add_global(name.clone(), minijinja::Value::from_serialize(value));
     }
 
     // Load in custom rust functions:
@@ -58,8 +60,11 @@ pub fn new_mini_env<'a>(
 
     // Load in any custom extensions to the PY_USER_FUNCS global:
     let custom_funcs = py_interface::load_custom_exts(&state.conf.engine.custom_extensions, state)?;
+    let mut taken = HashSet::from(["env_default".to_string()]);
     for (name, py_fn) in custom_funcs.into_iter() {
         debug!("Registering custom function: '{}'", name);
+        tracker.add_custom_func(&name);
+        taken.insert(name.clone());
 
         // Confirm doesn't clash with config var:
         if state.ctx.contains_key(&name) {
@@ -110,25 +115,90 @@ pub fn new_mini_env<'a>(
         }
     }
 
+    // Last, so preloaded templates can use everything else:
+    add_preloads(&mut env, state, tracker, &mut taken)?;
+
     Ok(env)
 }
 
-fn custom_loader<'x, P: AsRef<Path> + 'x>(
-    dir: P,
-) -> impl for<'a> Fn(&'a str) -> core::result::Result<Option<String>, minijinja::Error>
-       + Send
-       + Sync
-       + 'static {
-    let dir = dir.as_ref().to_path_buf();
-    move |name| match fs::read_to_string(dir.join(name)) {
-        Ok(result) => Ok(Some(result)),
-        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
-        Err(err) => Err(minijinja::Error::new(
-            minijinja::ErrorKind::InvalidOperation,
-            "could not read template",
-        )
-        .with_source(err)),
+/// The engine options a template renders with when they differ from the config's [engine], None otherwise.
+///
+/// The first matching engine.overrides entry applies, with any options in the template's front matter on top.
+pub fn template_engine(state: &State, template: &Template) -> Result<Option<Engine>, Report<Zerr>> {
+    let mut engine = None;
+    for engine_override in state.conf.engine.overrides.iter() {
+        if config_globs(&engine_override.globs, "engine.overrides")?
+            .matched(&template.rel_path, false)
+            .is_whitelist()
+        {
+            engine = Some(state.conf.engine.with_options(&engine_override.options)?);
+            break;
+        }
     }
+    let base = engine.as_ref().unwrap_or(&state.conf.engine);
+    Ok(template.front.engine(base)?.or(engine))
+}
+
+/// A copy of the environment using the template's own engine options or escape, None when neither differ so the shared one can be used.
+///
+/// Nothing loaded is shared, so the template's includes are also parsed with its syntax.
+/// The escape is otherwise picked by the template's name, which differs from its output's with front matter out, foreach or custom matchers.
+pub fn template_env<'a>(
+    env: &minijinja::Environment<'a>,
+    state: &State,
+    template: &Template,
+) -> Result<Option<minijinja::Environment<'a>>, Report<Zerr>> {
+    let engine = template_engine(state, template)?;
+    let options = engine.as_ref().unwrap_or(&state.conf.engine);
+    let matching = Matching::new(&state.conf);
+    let out_escape = escape::out_escape(&template.out_rel_path, &options.autoescape);
+    if engine.is_none()
+        && escape::auto_escape(&template.rel_path, &matching, &options.autoescape) == out_escape
+    {
+        return Ok(None);
+    }
+    let mut env = env.clone();
+    env.clear_templates();
+    set_options(
+        &mut env,
+        options,
+        &matching,
+        Some((template.rel_path.clone(), out_escape)),
+    )?;
+    Ok(Some(env))
+}
+
+/// Apply the user configurable engine options: syntax, whitespace, undefined behavior, limits and autoescape.
+///
+/// The escape of a template by its output can be given, everything else is escaped by its name.
+fn set_options(
+    env: &mut minijinja::Environment,
+    engine: &Engine,
+    matching: &Matching,
+    own_escape: Option<(String, AutoEscape)>,
+) -> Result<(), Report<Zerr>> {
+    env.set_syntax(
+        SyntaxConfig::builder()
+            .block_delimiters(engine.block_start.clone(), engine.block_end.clone())
+            .variable_delimiters(engine.variable_start.clone(), engine.variable_end.clone())
+            .comment_delimiters(engine.comment_start.clone(), engine.comment_end.clone())
+            .build()
+            .change_context(Zerr::ConfigInvalid)?,
+    );
+    env.set_trim_blocks(engine.trim_blocks);
+    env.set_lstrip_blocks(engine.lstrip_blocks);
+    env.set_undefined_behavior(engine.undefined.into());
+    env.set_recursion_limit(engine.recursion_limit);
+    env.set_fuel(engine.fuel);
+
+    // Off unless configured for the output's extension, escaping everything caused problems with e.g. adding strings around values in json files:
+    let autoescape = engine.autoescape.clone();
+    let matching = matching.clone();
+    env.set_auto_escape_callback(move |name| match own_escape.as_ref() {
+        Some((own_name, escape)) if own_name == name => *escape,
+        _ => escape::auto_escape(name, &matching, &autoescape),
+    });
+    Ok(())
 }
 
 fn gen_env_default_fn(
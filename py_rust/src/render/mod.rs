@@ -1,20 +1,103 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
 use colored::Colorize;
+use ignore::overrides::{Override, OverrideBuilder};
 use minijinja::context;
+use pyo3::prelude::*;
 
 mod args_validate;
 mod debug;
+mod deps;
+mod diff;
+mod escape;
+mod foreach;
+mod front_matter;
+mod loader;
 mod lockfile;
+mod matching;
 mod mini_env;
+mod names;
+mod postprocess;
+mod preload;
+mod regions;
+mod report;
+mod selection;
+mod staging;
 mod template;
 mod walker;
+mod watch;
 pub use lockfile::hash_contents;
 pub use walker::get_template_matcher_rewrite_mapping;
+pub use watch::watch;
 
 use crate::{
-    args::RenderCommand, prelude::*, render::mini_env::new_mini_env, state::State,
+    args::RenderCommand,
+    prelude::*,
+    render::{
+        deps::Tracker,
+        matching::Matching,
+        mini_env::{new_mini_env, template_env},
+        postprocess::Postprocess,
+        selection::Selection,
+        template::Template,
+    },
+    state::State,
     utils::timing::format_duration,
 };
 
+/// The templates handled by a render in the order they were found, with any orphaned outputs deleted.
+#[derive(Debug, Default)]
+struct Rendered {
+    templates: Vec<Handled>,
+    /// Outputs of templates that no longer exist which were deleted, relative to the root.
+    orphans_deleted: Vec<String>,
+}
+
+impl Rendered {
+    fn push(&mut self, template: Template, status: Status, duration: Duration) {
+        self.templates.push(Handled {
+            template,
+            status,
+            duration,
+        });
+    }
+
+    fn with_status(&self, status: Status) -> impl Iterator<Item = &Template> {
+        self.templates
+            .iter()
+            .filter(move |handled| handled.status == status)
+            .map(|handled| &handled.template)
+    }
+
+    fn count(&self, status: Status) -> usize {
+        self.with_status(status).count()
+    }
+}
+
+#[derive(Debug)]
+struct Handled {
+    template: Template,
+    status: Status,
+    /// Time spent rendering, or checking the inputs when skipped.
+    duration: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    /// Output changed and was written.
+    Written,
+    /// Re-rendered, but the output matched the lockfile.
+    Identical,
+    /// Not re-rendered, as none of the inputs changed since the last render.
+    Skipped,
+    /// Failed to render, nothing is written when any fail.
+    Failed,
+}
+
 pub fn render(args: &crate::args::Args, render_args: &RenderCommand) -> Result<bool, Report<Zerr>> {
     args_validate::args_validate(render_args)?;
 
@@ -26,35 +109,70 @@ pub fn render(args: &crate::args::Args, render_args: &RenderCommand) -> Result<b
     state.load_all_vars()?;
     debug!("State: {:#?}", state);
 
-    let (written, identical) = render_inner(&state, render_args, &mut lockfile)?;
+    let mut rendered = Rendered::default();
+    // Pre tasks will have already run when loading the state, apart from in check mode:
+    let mut num_tasks = if state.light || render_args.check {
+        0
+    } else {
+        state.conf.tasks.pre.len()
+    };
+    // Kept separate so the report can still be written when something fails:
+    let result = (|| {
+        render_inner(&state, render_args, &mut lockfile, &mut rendered)?;
 
-    // Run post-tasks only if not light/superlight:
-    if !state.light {
-        state.conf.tasks.run_post(&state)?;
-    }
+        // Check mode shouldn't modify anything, so skips post tasks too:
+        if render_args.check {
+            return check_outdated(&rendered);
+        }
 
-    timeit!("Syncing lockfile", { lockfile.sync() })?;
+        // Synced straight after the outputs are moved into place, so it matches them even if a post task fails:
+        rendered.orphans_deleted =
+            timeit!("Syncing lockfile", { lockfile.sync(state.conf.orphans) })?;
+
+        // Run post-tasks only if not light/superlight:
+        if !state.light {
+            state.conf.tasks.run_post(&state)?;
+            num_tasks += state.conf.tasks.post.len();
+            lockfile.record_post_task_changes()?;
+        }
+        Ok(())
+    })();
+
+    if let Some(target) = &render_args.report {
+        report::write(
+            target,
+            &state,
+            render_args,
+            &rendered,
+            num_tasks,
+            lockfile.modified,
+            result.as_ref().err(),
+        )?;
+    }
+    result?;
 
     // Write only when hidden cli flag --debug is set, to allow testing internals from python without having to setup custom interfaces:
     if render_args.debug {
         let debug = debug::Debug {
             conf: state.conf.clone(),
             ctx: state.ctx.clone(),
-            written: written
-                .iter()
+            written: rendered
+                .with_status(Status::Written)
                 .map(|t| t.out_path.display().to_string())
                 .collect(),
-            identical: identical.iter().map(|t| t.rel_path.clone()).collect(),
-            matched_templates: {
-                let mut all = vec![];
-                for tmpl in written.iter() {
-                    all.push(tmpl.rel_path.clone())
-                }
-                for tmpl in identical.iter() {
-                    all.push(tmpl.rel_path.clone())
-                }
-                all
-            },
+            identical: rendered
+                .with_status(Status::Identical)
+                .map(|t| t.rel_path.clone())
+                .collect(),
+            skipped: rendered
+                .with_status(Status::Skipped)
+                .map(|t| t.rel_path.clone())
+                .collect(),
+            matched_templates: rendered
+                .templates
+                .iter()
+                .map(|handled| handled.template.rel_path.clone())
+                .collect(),
             lockfile_modified: lockfile.modified,
         };
 
@@ -65,148 +183,679 @@ pub fn render(args: &crate::args::Args, render_args: &RenderCommand) -> Result<b
             .change_context(Zerr::InternalError)?;
     }
 
-    let num_tasks = if state.light {
-        0
+    // The report replaces the summary when printed to stdout:
+    if render_args.report_to_stdout() {
+        return Ok(true);
+    }
+
+    let elapsed = GLOBAL_TIME_RECORDER
+        .total_elapsed()
+        .change_context(Zerr::InternalError)?;
+    if render_args.check {
+        let num_identical = rendered.count(Status::Identical);
+        println!(
+            "{} {} template{} up to date. {} elapsed.",
+            "zetch:".bold(),
+            num_identical,
+            if num_identical == 1 { "" } else { "s" },
+            format_duration(elapsed)
+        );
     } else {
-        state.conf.tasks.pre.len() + state.conf.tasks.post.len()
-    };
+        print_summary(&rendered, num_tasks, lockfile.modified, elapsed);
+    }
+
+    Ok(true)
+}
+
+/// In check mode, fail listing every template whose output would change.
+fn check_outdated(rendered: &Rendered) -> Result<(), Report<Zerr>> {
+    let num_written = rendered.count(Status::Written);
+    if num_written == 0 {
+        return Ok(());
+    }
+
+    let mut report = zerr!(
+        Zerr::CheckFailed,
+        "{} template{} out of date, rerun 'zetch render' to update:",
+        num_written,
+        if num_written == 1 { " is" } else { "s are" }
+    );
+    for template in rendered.with_status(Status::Written) {
+        report = report.attach_printable(format!(
+            "{} -> {}",
+            template.rel_path,
+            template.out_path.display()
+        ));
+    }
+    Err(report)
+}
+
+/// Print the one line summary of a render.
+fn print_summary(
+    rendered: &Rendered,
+    num_tasks: usize,
+    lockfile_modified: bool,
+    elapsed: std::time::Duration,
+) {
+    let num_written = rendered.count(Status::Written);
+    let num_skipped = rendered.count(Status::Skipped);
     println!(
-        "{} {} template{} written, {} identical.{} Lockfile {}. {} elapsed.",
+        "{} {} template{} written, {} identical{}.{}{} Lockfile {}. {} elapsed.",
         "zetch:".bold(),
-        written.len(),
-        if written.len() == 1 { "" } else { "s" },
-        identical.len(),
+        num_written,
+        if num_written == 1 { "" } else { "s" },
+        rendered.count(Status::Identical),
+        if num_skipped == 0 {
+            "".to_string()
+        } else {
+            format!(", {num_skipped} skipped")
+        },
+        if rendered.orphans_deleted.is_empty() {
+            "".to_string()
+        } else {
+            format!(
+                " {} orphaned output{} deleted: {}.",
+                rendered.orphans_deleted.len(),
+                if rendered.orphans_deleted.len() == 1 {
+                    ""
+                } else {
+                    "s"
+                },
+                rendered.orphans_deleted.join(", ")
+            )
+        },
         if num_tasks > 0 {
             format!(" {num_tasks} tasks run.").to_string()
         } else {
             "".to_string()
         },
-        if lockfile.modified {
+        if lockfile_modified {
             "modified"
         } else {
             "unchanged"
         },
-        format_duration(
-            GLOBAL_TIME_RECORDER
-                .total_elapsed()
-                .change_context(Zerr::InternalError)?
-        )
+        format_duration(elapsed)
     );
-
-    Ok(true)
 }
 
 fn render_inner(
     state: &State,
     render_args: &RenderCommand,
     lockfile: &mut self::lockfile::Lockfile,
-) -> Result<
-    (
-        Vec<crate::render::template::Template>,
-        Vec<crate::render::template::Template>,
-    ),
-    Report<Zerr>,
-> {
-    let walker = timeit!("Filesystem walker creation", {
-        self::walker::create(&render_args.root, state)
+    rendered: &mut Rendered,
+) -> Result<(), Report<Zerr>> {
+    let selection = Selection::new(&render_args.root, render_args)?;
+    let templates = select_templates(
+        find_templates(&render_args.root, state)?,
+        &selection,
+        lockfile,
+    );
+
+    // Create the minijinja environment with the context.
+    // A loader is set that can automatically load templates, this means it can load the main templates, and any other "includes" in user templates too.
+    let mut tracker = Tracker::new(&render_args.root, state)?;
+    let env = timeit!("Creating rendering environment", {
+        new_mini_env(&render_args.root, state, &mut tracker)
     })?;
 
-    let templates = timeit!("Traversing filesystem & identifying templates", {
-        self::walker::find_templates(&render_args.root, walker, state.conf.matchers.as_slice())
+    render_templates(
+        &env,
+        &tracker,
+        state,
+        templates,
+        render_args,
+        lockfile,
+        rendered,
+    )
+}
+
+/// Walk the root and identify all templates, respecting the configured excludes and ignore files.
+fn find_templates(root: &std::path::Path, state: &State) -> Result<Vec<Template>, Report<Zerr>> {
+    let walker = timeit!("Filesystem walker creation", {
+        self::walker::create(root, state)
     })?;
 
-    let mut identical = Vec::new();
-    let mut written = Vec::new();
+    timeit!("Traversing filesystem & identifying templates", {
+        self::walker::find_templates(
+            root,
+            walker,
+            &self::walker::excludes(root, state)?,
+            &Matching::new(&state.conf),
+            Some(&config_globs(&state.conf.regions, "regions")?),
+            &config_globs(&state.conf.verbatim, "verbatim")?,
+        )
+    })
+}
+
+/// Filter down to the selected templates, the lockfile entries of the rest are kept as they are.
+fn select_templates(
+    templates: Vec<Template>,
+    selection: &Selection,
+    lockfile: &mut self::lockfile::Lockfile,
+) -> Vec<Template> {
+    if selection.is_all() {
+        return templates;
+    }
 
-    // Create the minijinja environment with the context.
-    // A loader is set that can automatically load templates, this means it can load the main templates, and any other "includes" in user templates too.
-    let env = timeit!("Creating rendering environment", {
-        new_mini_env(&render_args.root, state)
+    lockfile.keep_unselected(|rel_path| selection.is_selected(rel_path));
+    let (selected, unselected): (Vec<_>, Vec<_>) = templates
+        .into_iter()
+        .partition(|t| selection.is_selected(&t.rel_path));
+    // Their outputs mustn't be treated as orphaned either, even when not yet in the lockfile:
+    for template in unselected.iter() {
+        lockfile.keep_template(template);
+    }
+
+    if selected.is_empty() {
+        warn!("No templates matched the given template paths or --only globs.");
+    }
+    selected
+}
+
+/// Render the given templates with an already created environment, syncing outputs with the lockfile.
+///
+/// Nothing is written unless every template succeeds.
+///
+/// Templates whose inputs haven't changed since they were last rendered are skipped,
+/// apart from in check mode, where the real outputs on disk need comparing.
+///
+/// Each handled template is added to rendered, including any that failed.
+fn render_templates(
+    env: &minijinja::Environment,
+    tracker: &Tracker,
+    state: &State,
+    templates: Vec<Template>,
+    render_args: &RenderCommand,
+    lockfile: &mut self::lockfile::Lockfile,
+    rendered: &mut Rendered,
+) -> Result<(), Report<Zerr>> {
+    let jobs = render_args.jobs.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    });
+
+    let templates = timeit!("Resolving template outputs", {
+        foreach::expand(env, state, &render_args.root, templates)
+            .and_then(|templates| names::render(env, state, &render_args.root, templates))
     })?;
+    // Expanded and rendered outputs can conflict too:
+    self::walker::check_outputs(
+        &templates,
+        &self::walker::excludes(&render_args.root, state)?,
+    )?;
 
-    timeit!("Rendering templates & syncing files", {
-        for template in templates {
-            debug!("Rendering template: {}", template.rel_path);
-            let tmpl = match env.get_template(&template.rel_path) {
-                Ok(tmpl) => Ok(tmpl),
-                Err(e) => match e.kind() {
-                    minijinja::ErrorKind::BadEscape => Err(e).change_context(Zerr::RenderTemplateError).attach_printable("Bad string escape in template. If windows filepaths being used in the template, make sure they've been escaped with an extra backslash. E.g. '.\\\\Desktop\\\\file.txt'"),
-                    _ => Err(e).change_context(Zerr::InternalError),
-                },
-            }?;
-
-            let compiled = match tmpl.render(context! {}) {
-                Ok(compiled) => compiled,
-                Err(e) => {
-                    let mut out_e = zerr!(Zerr::RenderTemplateError, "Failed to render template.")
-                        .attach_printable(format!("{e}"));
-
-                    // Rendering failed, important here to give a really nice error as common user error.
-                    // So print the lines around the error if possible:
-                    if let Some(err_line_no) = e.line() {
-                        let source_code = std::fs::read_to_string(&template.path)
-                            .change_context(Zerr::InternalError)?;
-                        let lines = source_code.lines().collect::<Vec<_>>();
-                        let start_line_no = if err_line_no > 3 { err_line_no - 3 } else { 1 };
-                        let end_line_no = (err_line_no + 3).min(lines.len());
-                        let mut s = String::new();
-                        for line_no in start_line_no..(end_line_no + 1) {
-                            // Handle keeping aligned, e.g. line numbers start in single digits but go into double digits:
-                            let extra_indent = " "
-                                .repeat(end_line_no.to_string().len() - line_no.to_string().len());
-                            let line = lines[line_no - 1];
-                            if line_no == err_line_no {
-                                // If possible, identify the portion of the line causing the error, making it bright red and underlined:
-                                // This will only be known in some cases, e.g. mj exposes it for {{ REE + 1 }} when REE is undefined but not {{ REE }}
-                                let fmtted_line = if let Some(source_range) = e.range() {
-                                    // The range is of the entire template, not the line, so need to normalise it for the line:
-                                    let mut offset = 0;
-                                    for l in lines.iter().take(line_no - 1) {
-                                        offset += l.len() + 1;
-                                    }
-                                    let line_range =
-                                        source_range.start - offset..source_range.end - offset;
-                                    // If line range finishes greater than the length of the line, raise internal error as it's something wrong with this block:
-                                    if line_range.end > line.len() {
-                                        return Err(zerr!(
-                                            Zerr::InternalError,
-                                            "Line range end is greater than line length."
-                                        ));
-                                    }
-                                    format!(
-                                        "{}{}{}",
-                                        &line[..line_range.start],
-                                        &line[line_range.clone()].underline().bright_red(),
-                                        &line[line_range.end..]
-                                    )
-                                } else {
-                                    line.to_string()
-                                };
-                                s.push_str(&format!(
-                                    "{}",
-                                    format!("{extra_indent}{line_no}| {fmtted_line} <-- ERR\n")
-                                        .red()
-                                        .bold()
-                                ));
-                            } else {
-                                s.push_str(&format!("{extra_indent}{line_no}| {line}\n"));
+    let postprocess = Postprocess::new(state)?;
+    let processed = timeit!("Rendering templates", {
+        let lockfile: &self::lockfile::Lockfile = lockfile;
+        process_templates(&templates, jobs, render_args.fail_fast, |template| {
+            process_template(
+                env,
+                tracker,
+                state,
+                template,
+                render_args,
+                lockfile,
+                &postprocess,
+            )
+        })
+    })?;
+
+    // Templates that weren't processed due to an earlier failure with --fail-fast are left out:
+    let mut outcomes = vec![];
+    let mut errs = vec![];
+    for (template, processed) in templates.into_iter().zip(processed) {
+        match processed {
+            Some((Ok(outcome), duration)) => outcomes.push((template, outcome, duration)),
+            Some((Err(e), duration)) => {
+                errs.push((template.rel_path.clone(), e));
+                rendered.push(template, Status::Failed, duration);
+            }
+            None => {}
+        }
+    }
+    // Reported by path, the walk order isn't meaningful:
+    errs.sort_by(|a, b| a.0.cmp(&b.0));
+    if let Some(e) = combine_errors(errs.into_iter().map(|(_, e)| e).collect()) {
+        return Err(e);
+    }
+
+    if !render_args.check && !render_args.force {
+        check_overwrites(state, &outcomes, lockfile)?;
+    }
+
+    // Applied in the original order, so the summary and lockfile don't depend on which thread finished first:
+    timeit!("Syncing files", {
+        let synced = (|| {
+            for (template, outcome, duration) in outcomes {
+                match outcome {
+                    Outcome::Skipped => {
+                        lockfile.keep_template(&template);
+                        rendered.push(template, Status::Skipped, duration);
+                    }
+                    // Not kept, so any output from before it was excluded is orphaned:
+                    Outcome::Excluded => rendered.push(template, Status::Skipped, duration),
+                    Outcome::Compiled {
+                        compiled,
+                        deps,
+                        mode,
+                    } => {
+                        let existing = if render_args.diff || render_args.check {
+                            read_existing(&template)?
+                        } else {
+                            None
+                        };
+                        // Has to be prepared before the new contents are written, binary contents can't be shown:
+                        let diff = match std::str::from_utf8(&compiled) {
+                            Ok(compiled_str)
+                                if render_args.diff
+                                    && existing.as_deref() != Some(compiled.as_slice()) =>
+                            {
+                                Some(diff::unified_diff(
+                                    &template.out_rel_path,
+                                    existing.as_deref().map(String::from_utf8_lossy).as_deref(),
+                                    compiled_str,
+                                ))
+                            }
+                            _ => None,
+                        };
+
+                        let is_new = match deps {
+                            Some(deps) => lockfile.add_template(&template, compiled, deps, mode)?,
+                            // Check mode compares with the real contents and permissions on disk:
+                            None => {
+                                existing.as_deref() != Some(compiled.as_slice())
+                                    || (existing.is_some()
+                                        && mode.is_some()
+                                        && self::lockfile::file_mode(&template.out_path)? != mode)
+                            }
+                        };
+                        if is_new {
+                            // Kept out of the way of a report printed to stdout, so it can still be parsed:
+                            match diff {
+                                Some(diff) if render_args.report_to_stdout() => eprint!("{diff}"),
+                                Some(diff) => print!("{diff}"),
+                                None => {}
                             }
+                            rendered.push(template, Status::Written, duration);
+                        } else {
+                            rendered.push(template, Status::Identical, duration);
                         }
-                        out_e = out_e.attach_printable(s);
                     }
-
-                    return Err(out_e);
                 }
+            }
+            Ok::<_, error_stack::Report<Zerr>>(())
+        })();
+
+        // Outputs are staged until everything's succeeded, so a failure leaves the tree as it was:
+        match synced {
+            Ok(()) => lockfile.commit_outputs(),
+            Err(e) => {
+                lockfile.discard_outputs();
+                Err(e)
+            }
+        }
+    })
+}
+
+/// Combine the failures of each template into a single report, so they can all be fixed in one go.
+fn combine_errors(errs: Vec<Report<Zerr>>) -> Option<Report<Zerr>> {
+    let num_errs = errs.len();
+    let mut errs = errs.into_iter();
+    let mut combined = errs.next()?;
+    if num_errs == 1 {
+        return Some(combined);
+    }
+    for e in errs {
+        combined.extend_one(e);
+    }
+    Some(
+        combined
+            .change_context(Zerr::RenderTemplateError)
+            .attach_printable(format!(
+                "{num_errs} templates failed to render, nothing has been written."
+            )),
+    )
+}
+
+/// Make sure none of the outputs about to be written would clobber a file zetch didn't write, or one that's been modified since last rendered.
+///
+/// All are checked before anything's written, outputs matching allow_overwrite in the config are exempt.
+fn check_overwrites(
+    state: &State,
+    outcomes: &[(Template, Outcome, Duration)],
+    lockfile: &self::lockfile::Lockfile,
+) -> Result<(), Report<Zerr>> {
+    let allowed = config_globs(&state.conf.allow_overwrite, "allow_overwrite")?;
+
+    let mut conflicts = vec![];
+    for (template, outcome, _) in outcomes {
+        if let Outcome::Compiled { compiled, .. } = outcome {
+            if allowed
+                .matched(&template.out_rel_path, false)
+                .is_whitelist()
+            {
+                continue;
+            }
+            if let Some(reason) = lockfile.overwrite_conflict(template, compiled)? {
+                conflicts.push(format!("{} {}", template.out_rel_path, reason));
+            }
+        }
+    }
+
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+
+    let mut report = zerr!(
+        Zerr::OverwriteRefused,
+        "Refusing to overwrite {} output{}, nothing has been written. Use --force or add to allow_overwrite in the config to overwrite anyway:",
+        conflicts.len(),
+        if conflicts.len() == 1 { "" } else { "s" }
+    );
+    for conflict in conflicts {
+        report = report.attach_printable(conflict);
+    }
+    Err(report)
+}
+
+/// Build a matcher from git-style globs in the config, matched against paths relative to the root.
+fn config_globs(globs: &[String], field: &str) -> Result<Override, Report<Zerr>> {
+    let mut builder = OverrideBuilder::new(".");
+    for glob in globs.iter() {
+        builder
+            .add(glob)
+            .change_context(Zerr::ConfigInvalid)
+            .attach_printable_lazy(|| format!("Invalid {field} glob: '{glob}'."))?;
+    }
+    builder.build().change_context(Zerr::ConfigInvalid)
+}
+
+/// The result of processing a single template, before anything's written to disk.
+enum Outcome {
+    /// Inputs unchanged since the last render, so not rendered.
+    Skipped,
+    /// The skip_if in the template's front matter is true, so it has no output.
+    Excluded,
+    /// Rendered, deps are None in check mode as the lockfile isn't being updated.
+    Compiled {
+        compiled: Vec<u8>,
+        deps: Option<self::deps::Deps>,
+        /// The permissions the output should have, None when left as the defaults.
+        mode: Option<u32>,
+    },
+}
+
+/// Process templates across a pool of threads sharing the environment, the results are in the same order as the templates, with the time each took.
+///
+/// With fail_fast, no more templates are started after a failure, those left unprocessed are None.
+/// As templates are handed out in order, everything before the first failure will always have been processed, the same as processing them one by one.
+fn process_templates(
+    templates: &[Template],
+    jobs: usize,
+    fail_fast: bool,
+    process: impl Fn(&Template) -> Result<Outcome, Report<Zerr>> + Sync,
+) -> Result<Vec<Option<(Result<Outcome, Report<Zerr>>, Duration)>>, Report<Zerr>> {
+    let timed = |template: &Template| {
+        let start = Instant::now();
+        let result = process(template);
+        (result, start.elapsed())
+    };
+
+    let jobs = jobs.min(templates.len());
+    let mut processed = (0..templates.len()).map(|_| None).collect::<Vec<_>>();
+    if jobs <= 1 {
+        for (idx, template) in templates.iter().enumerate() {
+            let (result, duration) = timed(template);
+            let failed = result.is_err();
+            processed[idx] = Some((result, duration));
+            if failed && fail_fast {
+                break;
+            }
+        }
+        return Ok(processed);
+    }
+
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let worker = || {
+        let mut results = vec![];
+        while !failed.load(Ordering::Relaxed) {
+            let idx = next.fetch_add(1, Ordering::Relaxed);
+            let Some(template) = templates.get(idx) else {
+                break;
             };
-            let is_new = lockfile.add_template(&template, compiled)?;
-            if is_new {
-                written.push(template);
-            } else {
-                identical.push(template);
+            let (result, duration) = timed(template);
+            if result.is_err() && fail_fast {
+                failed.store(true, Ordering::Relaxed);
             }
+            results.push((idx, result, duration));
         }
-        Ok::<_, error_stack::Report<Zerr>>(())
+        results
+    };
+
+    // Custom python functions acquire the GIL when called, so it needs releasing whilst the workers run:
+    let per_worker = Python::with_gil(|py| {
+        py.allow_threads(|| {
+            std::thread::scope(|scope| {
+                let handles = (0..jobs).map(|_| scope.spawn(worker)).collect::<Vec<_>>();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join())
+                    .collect::<Result<Vec<_>, _>>()
+            })
+        })
+    })
+    .map_err(|thread_err| {
+        zerr!(Zerr::InternalError, "Error reading thread result.")
+            .attach_printable(format!("Thread error: {thread_err:?}"))
+    })?;
+
+    for (idx, result, duration) in per_worker.into_iter().flatten() {
+        processed[idx] = Some((result, duration));
+    }
+    Ok(processed)
+}
+
+/// Render a single template, or skip it when its inputs are unchanged since the last render.
+fn process_template(
+    env: &minijinja::Environment,
+    tracker: &Tracker,
+    state: &State,
+    template: &Template,
+    render_args: &RenderCommand,
+    lockfile: &self::lockfile::Lockfile,
+    postprocess: &Postprocess,
+) -> Result<Outcome, Report<Zerr>> {
+    // Engine options in the front matter, or an output escaped differently to the name, need their own environment:
+    let own_env = template_env(env, state, template)?;
+    let env = own_env.as_ref().unwrap_or(env);
+    let ctx = template.context();
+
+    if let Some(skip_if) = template.front.skip_if.as_ref() {
+        let skip = env
+            .compile_expression(skip_if)
+            .and_then(|expr| expr.eval(&ctx))
+            .map_err(|e| {
+                zerr!(
+                    Zerr::RenderTemplateError,
+                    "Failed to evaluate skip_if '{}' in the front matter of '{}'.",
+                    skip_if,
+                    template.rel_path
+                )
+                .attach_printable(format!("{e}"))
+            })?;
+        if skip.is_true() {
+            debug!(
+                "Template '{}' skip_if is true, excluding.",
+                template.rel_path
+            );
+            return Ok(Outcome::Excluded);
+        }
+    }
+
+    let mode = postprocess.mode(template)?;
+    if !render_args.check
+        && template.front.cache
+        && lockfile.mode_unchanged(template, mode)
+        && lockfile.output_unchanged(template)
+    {
+        if let Some(recorded) = lockfile.deps(template) {
+            if tracker.is_fresh(state, template, recorded)? {
+                debug!(
+                    "Template '{}' inputs unchanged, skipping.",
+                    template.rel_path
+                );
+                return Ok(Outcome::Skipped);
+            }
+        }
+    }
+
+    debug!("Rendering template: {}", template.rel_path);
+    let compiled = if template.verbatim {
+        // Copied as it is, so not formatted or validated either:
+        std::fs::read(&template.path).change_context(Zerr::InternalError)?
+    } else {
+        let compiled = if template.regions {
+            render_regions(env, template)?
+        } else {
+            // Syntax errors are the user's to fix, so reported with the lines around them like any other render failure:
+            let tmpl = env.get_template(&template.rel_path).map_err(|e| match e.kind() {
+                minijinja::ErrorKind::BadEscape => render_error(template, e).attach_printable("Bad string escape in template. If windows filepaths being used in the template, make sure they've been escaped with an extra backslash. E.g. '.\\\\Desktop\\\\file.txt'"),
+                _ => render_error(template, e),
+            })?;
+            tmpl.render(&ctx).map_err(|e| render_error(template, e))?
+        };
+        postprocess.apply(state, template, compiled)?.into_bytes()
+    };
+
+    let deps = if render_args.check {
+        None
+    } else {
+        Some(tracker.collect(env, state, template)?)
+    };
+    Ok(Outcome::Compiled {
+        compiled,
+        deps,
+        mode,
+    })
+}
+
+/// Render each templated region of an ordinary file, returning the whole file with the region bodies replaced.
+fn render_regions(
+    env: &minijinja::Environment,
+    template: &Template,
+) -> Result<String, Report<Zerr>> {
+    let contents = std::fs::read_to_string(&template.path).change_context(Zerr::InternalError)?;
+    let regions = regions::parse(&contents).attach_printable_lazy(|| {
+        format!("Invalid templated regions in '{}'.", template.rel_path)
     })?;
 
-    Ok((written, identical))
+    let rendered = regions
+        .iter()
+        .map(|region| {
+            // Named after the file, so includes are tracked against it:
+            env.render_named_str(&template.rel_path, &region.source, context! {})
+                .map_err(|e| {
+                    zerr!(Zerr::RenderTemplateError, "Failed to render template.")
+                        .attach_printable(format!("{e}"))
+                        .attach_printable(format!(
+                            "In the templated region starting on line {} of '{}'.",
+                            region.line_no, template.rel_path
+                        ))
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let compiled = regions::splice(&contents, &regions, &rendered);
+
+    // The regions need finding again on the next render:
+    if !regions::parse(&compiled).is_ok_and(|parsed| parsed.len() == regions.len()) {
+        return Err(zerr!(
+            Zerr::RenderTemplateError,
+            "A templated region in '{}' rendered region markers, which would break the file's regions.",
+            template.rel_path
+        ));
+    }
+    Ok(compiled)
+}
+
+/// Rendering failed, important here to give a really nice error as common user error.
+fn render_error(template: &Template, e: minijinja::Error) -> Report<Zerr> {
+    let out_e = zerr!(Zerr::RenderTemplateError, "Failed to render template.")
+        .attach_printable(format!("{e}"));
+    match error_lines(template, &e) {
+        Ok(Some(lines)) => out_e.attach_printable(lines),
+        Ok(None) => out_e,
+        Err(internal) => internal,
+    }
+}
+
+/// Format the lines around the error if its location is known.
+fn error_lines(template: &Template, e: &minijinja::Error) -> Result<Option<String>, Report<Zerr>> {
+    let Some(err_line_no) = e.line() else {
+        return Ok(None);
+    };
+
+    let raw_source = std::fs::read_to_string(&template.path).change_context(Zerr::InternalError)?;
+    // The error is relative to the rendered body, numbered as in the file below by adding the front matter's lines back on:
+    let source_code = front_matter::body(&raw_source);
+    let front_lines = template.front.lines;
+    let lines = source_code.lines().collect::<Vec<_>>();
+    let start_line_no = if err_line_no > 3 { err_line_no - 3 } else { 1 };
+    let end_line_no = (err_line_no + 3).min(lines.len());
+    let mut s = String::new();
+    for line_no in start_line_no..(end_line_no + 1) {
+        // Handle keeping aligned, e.g. line numbers start in single digits but go into double digits:
+        let extra_indent = " ".repeat(
+            (end_line_no + front_lines).to_string().len()
+                - (line_no + front_lines).to_string().len(),
+        );
+        let file_line_no = line_no + front_lines;
+        let line = lines[line_no - 1];
+        if line_no == err_line_no {
+            // If possible, identify the portion of the line causing the error, making it bright red and underlined:
+            // This will only be known in some cases, e.g. mj exposes it for {{ REE + 1 }} when REE is undefined but not {{ REE }}
+            let fmtted_line = if let Some(source_range) = e.range() {
+                // The range is of the entire template, not the line, so need to normalise it for the line:
+                let mut offset = 0;
+                for l in lines.iter().take(line_no - 1) {
+                    offset += l.len() + 1;
+                }
+                let line_range = source_range.start - offset..source_range.end - offset;
+                // If line range finishes greater than the length of the line, raise internal error as it's something wrong with this block:
+                if line_range.end > line.len() {
+                    return Err(zerr!(
+                        Zerr::InternalError,
+                        "Line range end is greater than line length."
+                    ));
+                }
+                format!(
+                    "{}{}{}",
+                    &line[..line_range.start],
+                    line[line_range.clone()].underline().bright_red(),
+                    &line[line_range.end..]
+                )
+            } else {
+                line.to_string()
+            };
+            s.push_str(&format!(
+                "{}",
+                format!("{extra_indent}{file_line_no}| {fmtted_line} <-- ERR\n")
+                    .red()
+                    .bold()
+            ));
+        } else {
+            s.push_str(&format!("{extra_indent}{file_line_no}| {line}\n"));
+        }
+    }
+    Ok(Some(s))
+}
+
+/// Read the current contents of a template's output on disk, None when it doesn't exist yet.
+fn read_existing(template: &Template) -> Result<Option<Vec<u8>>, Report<Zerr>> {
+    match std::fs::read(&template.out_path) {
+        Ok(existing) => Ok(Some(existing)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).change_context(Zerr::InternalError),
+    }
 }
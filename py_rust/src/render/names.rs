@@ -0,0 +1,55 @@
+use std::path::Path;
+
+use super::{
+    front_matter,
+    mini_env::{template_engine, template_env},
+    template::Template,
+};
+use crate::{prelude::*, state::State};
+
+/// Render variables in output paths, e.g. from a directory named "{{ APP_NAME }}" or a template named "{{ MODULE }}.zetch.py".
+pub fn render(
+    env: &minijinja::Environment,
+    state: &State,
+    root: &Path,
+    templates: Vec<Template>,
+) -> Result<Vec<Template>, Report<Zerr>> {
+    templates
+        .into_iter()
+        .map(|template| {
+            // Files with templated regions are rendered in place, whatever their name:
+            if template.regions {
+                return Ok(template);
+            }
+            let engine = template_engine(state, &template)?;
+            let engine = engine.as_ref().unwrap_or(&state.conf.engine);
+            if !template.out_rel_path.contains(&engine.variable_start)
+                && !template.out_rel_path.contains(&engine.block_start)
+            {
+                return Ok(template);
+            }
+
+            let own_env = template_env(env, state, &template)?;
+            let env = own_env.as_ref().unwrap_or(env);
+            let rendered = env
+                .render_str(&template.out_rel_path, template.context())
+                .map_err(|e| {
+                    zerr!(
+                        Zerr::RenderTemplateError,
+                        "Failed to render the output path '{}' of '{}'.",
+                        template.out_rel_path,
+                        template.rel_path
+                    )
+                    .attach_printable(format!("{e}"))
+                })?;
+            let out_path =
+                front_matter::resolve_out(root, root, &rendered).attach_printable_lazy(|| {
+                    format!(
+                        "Rendered from the output path '{}' of '{}'.",
+                        template.out_rel_path, template.rel_path
+                    )
+                })?;
+            Ok(template.with_out_path(root, out_path))
+        })
+        .collect()
+}
@@ -0,0 +1,111 @@
+use ignore::overrides::Override;
+
+use super::{config_globs, lockfile::file_mode, template::Template};
+use crate::{
+    config::{formatters::Formatter, modes::parse_mode},
+    prelude::*,
+    read_write::FileType,
+    state::State,
+};
+
+/// The config driven steps applied to each rendered output before it's hashed and written.
+pub struct Postprocess<'a> {
+    formatters: Vec<(Override, &'a Formatter)>,
+    validate: Override,
+    modes: Vec<(Override, u32)>,
+}
+
+impl<'a> Postprocess<'a> {
+    pub fn new(state: &'a State) -> Result<Self, Report<Zerr>> {
+        Ok(Self {
+            formatters: state
+                .conf
+                .formatters
+                .iter()
+                .map(|formatter| Ok((config_globs(&formatter.globs, "formatters")?, formatter)))
+                .collect::<Result<_, Report<Zerr>>>()?,
+            validate: config_globs(&state.conf.validate, "validate")?,
+            modes: state
+                .conf
+                .modes
+                .iter()
+                .map(|mode| {
+                    let parsed = parse_mode(&mode.mode).ok_or_else(|| {
+                        zerr!(
+                            Zerr::ConfigInvalid,
+                            "[modes]: invalid mode '{}', expected octal permissions like \"755\".",
+                            mode.mode
+                        )
+                    })?;
+                    Ok((config_globs(&mode.globs, "modes")?, parsed))
+                })
+                .collect::<Result<_, Report<Zerr>>>()?,
+        })
+    }
+
+    /// The permissions the output should have: from the front matter, the first matching config entry, or else the template's own.
+    ///
+    /// None where unix permissions don't exist, and for files with templated regions which are their own output.
+    pub fn mode(&self, template: &Template) -> Result<Option<u32>, Report<Zerr>> {
+        if !cfg!(unix) || template.regions {
+            return Ok(None);
+        }
+        if let Some(mode) = template.front.mode {
+            return Ok(Some(mode));
+        }
+        if let Some((_, mode)) = self
+            .modes
+            .iter()
+            .find(|(globs, _)| matches(globs, template))
+        {
+            return Ok(Some(*mode));
+        }
+        file_mode(&template.path)
+    }
+
+    /// Run the matching formatters in order, then validate the result.
+    pub fn apply(
+        &self,
+        state: &State,
+        template: &Template,
+        mut compiled: String,
+    ) -> Result<String, Report<Zerr>> {
+        for (globs, formatter) in self.formatters.iter() {
+            if matches(globs, template) {
+                compiled = formatter
+                    .format(&state.final_config_path, &template.out_path, &compiled)
+                    .attach_printable_lazy(|| {
+                        format!("Failed to format the output of '{}'.", template.rel_path)
+                    })?;
+            }
+        }
+        self.validate_output(template, &compiled)?;
+        Ok(compiled)
+    }
+
+    /// Make sure the output parses as the filetype of its extension, when it matches the validate globs in the config.
+    fn validate_output(&self, template: &Template, compiled: &str) -> Result<(), Report<Zerr>> {
+        if !matches(&self.validate, template) {
+            return Ok(());
+        }
+        let Some(filetype) = template
+            .out_path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(FileType::from_ext)
+        else {
+            return Ok(());
+        };
+
+        filetype.validate_file(compiled).attach_printable_lazy(|| {
+            format!(
+                "Template '{}' rendered invalid {:?} to '{}'.",
+                template.rel_path, filetype, template.out_rel_path
+            )
+        })
+    }
+}
+
+fn matches(globs: &Override, template: &Template) -> bool {
+    globs.matched(&template.out_rel_path, false).is_whitelist()
+}
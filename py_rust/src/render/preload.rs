@@ -0,0 +1,180 @@
+use std::{collections::HashSet, fmt, sync::Arc};
+
+use minijinja::value::{Enumerator, Object, ObjectRepr, Value};
+use self_cell::self_cell;
+
+use super::deps::Tracker;
+use crate::{config::engine::Preload, prelude::*, state::State};
+
+/// Add the exports of each of the config's engine.preload templates to the environment as globals.
+///
+/// Names already taken by context keys, custom functions or earlier preloads are rejected.
+pub fn add_preloads(
+    env: &mut minijinja::Environment<'static>,
+    state: &State,
+    tracker: &mut Tracker,
+    taken: &mut HashSet<String>,
+) -> Result<(), Report<Zerr>> {
+    for preload in state.conf.engine.preload.iter() {
+        // Evaluated once with the config's own engine options, whatever the syntax of the templates using it, and able to use earlier preloads:
+        let mut preload_env = env.clone();
+        // Otherwise the fuel used by every call would come out of the single evaluation's allowance:
+        preload_env.set_fuel(None);
+        let exports = load_exports(preload_env, preload)?;
+
+        let globals = match preload.alias.as_ref() {
+            Some(alias) => vec![(alias.clone(), Value::from_iter(exports))],
+            None => exports,
+        };
+        for (name, value) in globals {
+            debug!("Registering preloaded global: '{}'", name);
+            if state.ctx.contains_key(&name) || !taken.insert(name.clone()) {
+                return Err(zerr!(
+                    Zerr::ConfigInvalid,
+                    "Failed to preload '{}' from '{}' as it clashes with a context key, custom function or another preload.",
+                    name,
+                    preload.template
+                ));
+            }
+            // Macros can read any of the context, like custom functions:
+            tracker.add_custom_func(&name);
+            env.add_global(name, value);
+        }
+    }
+    Ok(())
+}
+
+/// The exported macros and top-level set values of a preload template.
+fn load_exports(
+    env: minijinja::Environment<'static>,
+    preload: &Preload,
+) -> Result<Vec<(String, Value)>, Report<Zerr>> {
+    let failed = || {
+        zerr!(
+            Zerr::ConfigInvalid,
+            "Failed to preload '{}' from engine.preload in the config.",
+            preload.template
+        )
+    };
+    let loaded = LoadedTemplate::try_new(env, |env| env.get_template(&preload.template))
+        .map_err(|e| failed().attach_printable(format!("{e}")))?;
+    let module = Arc::new(
+        Module::try_new(loaded, |loaded| loaded.borrow_dependent().eval_to_state(()))
+            .map_err(|e| failed().attach_printable(format!("{e}")))?,
+    );
+
+    let mut exports = module
+        .with_dependent(|_, state| {
+            state
+                .exports()
+                .into_iter()
+                .map(|name| state.lookup(name).map(|value| (name.to_string(), value)))
+                .collect::<Option<Vec<_>>>()
+        })
+        .ok_or_else(failed)?
+        .into_iter()
+        .map(|(name, value)| {
+            // Macros can only be called with the state they're defined in, so objects are proxied to it.
+            // They aren't a public type to be told apart from other objects, which behave the same proxied:
+            let value = if value.as_object().is_some() {
+                Value::from_object(Preloaded {
+                    module: module.clone(),
+                    value,
+                })
+            } else {
+                value
+            };
+            (name, value)
+        })
+        .collect::<Vec<_>>();
+    exports.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(exports)
+}
+
+type PreloadTemplate<'a> = minijinja::Template<'a, 'a>;
+
+self_cell!(
+    /// A preload template with the environment it's loaded from.
+    struct LoadedTemplate {
+        owner: minijinja::Environment<'static>,
+
+        #[covariant]
+        dependent: PreloadTemplate,
+    }
+
+    impl {Debug}
+);
+
+type PreloadState<'a> = minijinja::State<'a, 'a>;
+
+self_cell!(
+    /// The evaluated state of a preload template, kept for every call to its macros.
+    ///
+    /// Owned by the objects proxied to it, so dropped along with the environment they're globals of, e.g. when watch reloads.
+    struct Module {
+        owner: LoadedTemplate,
+
+        #[not_covariant]
+        dependent: PreloadState,
+    }
+
+    impl {Debug}
+);
+
+/// An object exported from a preload template, usable from any template.
+///
+/// Calls are made with the preload's state, everything else is passed straight through.
+#[derive(Debug)]
+struct Preloaded {
+    module: Arc<Module>,
+    value: Value,
+}
+
+impl Object for Preloaded {
+    fn repr(self: &Arc<Self>) -> ObjectRepr {
+        self.value
+            .as_object()
+            .map_or(ObjectRepr::Plain, |obj| obj.repr())
+    }
+
+    fn get_value(self: &Arc<Self>, key: &Value) -> Option<Value> {
+        self.value
+            .get_item(key)
+            .ok()
+            .filter(|value| !value.is_undefined())
+    }
+
+    fn enumerate(self: &Arc<Self>) -> Enumerator {
+        match self.value.try_iter() {
+            Ok(iter) => Enumerator::Values(iter.collect()),
+            Err(_) => Enumerator::NonEnumerable,
+        }
+    }
+
+    fn is_true(self: &Arc<Self>) -> bool {
+        self.value.is_true()
+    }
+
+    fn call(
+        self: &Arc<Self>,
+        _state: &minijinja::State<'_, '_>,
+        args: &[Value],
+    ) -> Result<Value, minijinja::Error> {
+        self.module
+            .with_dependent(|_, state| self.value.call(state, args))
+    }
+
+    fn call_method(
+        self: &Arc<Self>,
+        _state: &minijinja::State<'_, '_>,
+        method: &str,
+        args: &[Value],
+    ) -> Result<Value, minijinja::Error> {
+        self.module
+            .with_dependent(|_, state| self.value.call_method(state, method, args))
+    }
+
+    fn render(self: &Arc<Self>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.value, f)
+    }
+}
@@ -0,0 +1,124 @@
+use std::ops::Range;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::prelude::*;
+
+static MARKER_PREFIX: &str = "zetch:";
+static START_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(
+        r"^\s*{COMMENT_OPEN}\s*{}start(?:\s+(.*?))?\s*{COMMENT_CLOSE}\s*$",
+        MARKER_PREFIX
+    ))
+    .expect("Regex failed to compile")
+});
+static END_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(
+        r"^\s*{COMMENT_OPEN}\s*{}end\s*{COMMENT_CLOSE}\s*$",
+        MARKER_PREFIX
+    ))
+    .expect("Regex failed to compile")
+});
+/// Markers must be on their own line in a comment, e.g. "<!-- ... -->", "# ...", "// ..." or "/* ... */".
+const COMMENT_OPEN: &str = r"(?:<!--|#|//|/\*|--|;|%|\{#)";
+const COMMENT_CLOSE: &str = r"(?:-->|\*/|#\})?";
+
+/// A templated region inside an ordinary file, the lines between a start and end marker.
+///
+/// The template lives in the start marker, so the file is both the source and the output.
+#[derive(Debug)]
+pub struct Region {
+    /// The line number of the start marker.
+    pub line_no: usize,
+    /// The template text from the start marker.
+    pub source: String,
+    /// Byte range of the rendered lines between the markers.
+    pub body: Range<usize>,
+}
+
+/// Whether the file contains any region markers, unbalanced markers are included so parsing can report them.
+pub fn has_markers(contents: &str) -> bool {
+    // Cheap check first as most files won't:
+    contents.contains(MARKER_PREFIX)
+        && contents
+            .lines()
+            .any(|line| START_REGEX.is_match(line) || END_REGEX.is_match(line))
+}
+
+/// Find all regions in the file contents, erroring on unbalanced markers.
+pub fn parse(contents: &str) -> Result<Vec<Region>, Report<Zerr>> {
+    let mut regions = vec![];
+    // The start line number, template source and body start of the currently open region:
+    let mut open: Option<(usize, String, usize)> = None;
+    let mut offset = 0;
+    for (idx, line) in contents.split_inclusive('\n').enumerate() {
+        let line_no = idx + 1;
+        let line_start = offset;
+        offset += line.len();
+
+        let line = line.trim_end_matches(['\r', '\n']);
+        if let Some(caps) = START_REGEX.captures(line) {
+            if let Some((open_line_no, ..)) = open {
+                return Err(zerr!(
+                    Zerr::RenderTemplateError,
+                    "Line {}: found a new {}start marker before the region opened on line {} was closed with {}end.",
+                    line_no,
+                    MARKER_PREFIX,
+                    open_line_no,
+                    MARKER_PREFIX
+                ));
+            }
+            let source = caps.get(1).map_or("", |m| m.as_str()).to_string();
+            open = Some((line_no, source, offset));
+        } else if END_REGEX.is_match(line) {
+            let Some((start_line_no, source, body_start)) = open.take() else {
+                return Err(zerr!(
+                    Zerr::RenderTemplateError,
+                    "Line {}: found an {}end marker without a matching {}start.",
+                    line_no,
+                    MARKER_PREFIX,
+                    MARKER_PREFIX
+                ));
+            };
+            regions.push(Region {
+                line_no: start_line_no,
+                source,
+                body: body_start..line_start,
+            });
+        }
+    }
+
+    if let Some((open_line_no, ..)) = open {
+        return Err(zerr!(
+            Zerr::RenderTemplateError,
+            "Line {}: region is never closed with an {}end marker.",
+            open_line_no,
+            MARKER_PREFIX
+        ));
+    }
+    Ok(regions)
+}
+
+/// Replace the bodies of the regions with their newly rendered contents, everything else is left as is.
+pub fn splice(contents: &str, regions: &[Region], rendered: &[String]) -> String {
+    let mut out = String::with_capacity(contents.len());
+    let mut last = 0;
+    for (region, body) in regions.iter().zip(rendered) {
+        out.push_str(&contents[last..region.body.start]);
+        out.push_str(body);
+        // The end marker needs to stay on its own line:
+        if !body.is_empty() && !body.ends_with('\n') {
+            out.push('\n');
+        }
+        last = region.body.end;
+    }
+    out.push_str(&contents[last..]);
+    out
+}
+
+/// The file with the region bodies removed, i.e. everything that's an input to rendering it.
+pub fn skeleton(contents: &str, regions: &[Region]) -> String {
+    let empty = regions.iter().map(|_| String::new()).collect::<Vec<_>>();
+    splice(contents, regions, &empty)
+}
@@ -0,0 +1,127 @@
+use std::{path::Path, time::Duration};
+
+use error_stack::{AttachmentKind, FrameKind};
+
+use super::{Rendered, Status};
+use crate::{args::RenderCommand, prelude::*, state::State};
+
+/// The machine readable summary of a render, written with --report.
+#[derive(Debug, serde::Serialize)]
+struct RenderReport {
+    /// The resolved config file used.
+    config: String,
+    root: String,
+    check: bool,
+    templates: Vec<TemplateReport>,
+    orphans_deleted: Vec<String>,
+    tasks_run: usize,
+    lockfile_modified: bool,
+    error: Option<ErrorReport>,
+    timings: Vec<Timing>,
+    elapsed_ms: f64,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct TemplateReport {
+    /// Relative to the root, as are outputs.
+    source: String,
+    output: String,
+    status: Status,
+    duration_ms: f64,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct ErrorReport {
+    kind: String,
+    messages: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct Timing {
+    description: String,
+    elapsed_ms: f64,
+}
+
+/// Write the report to the given path, or stdout when '-'.
+pub fn write(
+    target: &Path,
+    state: &State,
+    render_args: &RenderCommand,
+    rendered: &Rendered,
+    tasks_run: usize,
+    lockfile_modified: bool,
+    error: Option<&Report<Zerr>>,
+) -> Result<(), Report<Zerr>> {
+    let report = RenderReport {
+        config: canonical(&state.final_config_path),
+        root: canonical(&render_args.root),
+        check: render_args.check,
+        templates: rendered
+            .templates
+            .iter()
+            .map(|handled| TemplateReport {
+                source: handled.template.rel_path.clone(),
+                output: handled.template.out_rel_path.clone(),
+                status: handled.status,
+                duration_ms: millis(handled.duration),
+            })
+            .collect(),
+        orphans_deleted: rendered.orphans_deleted.clone(),
+        tasks_run,
+        lockfile_modified,
+        error: error.map(error_report),
+        timings: GLOBAL_TIME_RECORDER
+            .durations()
+            .change_context(Zerr::InternalError)?
+            .into_iter()
+            .map(|(description, elapsed)| Timing {
+                description,
+                elapsed_ms: millis(elapsed),
+            })
+            .collect(),
+        elapsed_ms: millis(
+            GLOBAL_TIME_RECORDER
+                .total_elapsed()
+                .change_context(Zerr::InternalError)?,
+        ),
+    };
+
+    let json = serde_json::to_string_pretty(&report).change_context(Zerr::InternalError)?;
+    if target == Path::new("-") {
+        println!("{json}");
+        Ok(())
+    } else {
+        std::fs::write(target, json)
+            .change_context(Zerr::InternalError)
+            .attach_printable_lazy(|| format!("Failed to write report to '{}'.", target.display()))
+    }
+}
+
+/// The error's kind and messages, oldest first so it reads the same as the printed error.
+fn error_report(error: &Report<Zerr>) -> ErrorReport {
+    let mut messages = error
+        .frames()
+        .filter_map(|frame| match frame.kind() {
+            FrameKind::Attachment(AttachmentKind::Printable(printable)) => {
+                Some(printable.to_string())
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    messages.reverse();
+    ErrorReport {
+        kind: format!("{:?}", error.current_context()),
+        messages,
+    }
+}
+
+fn canonical(path: &Path) -> String {
+    path.canonicalize()
+        .unwrap_or_else(|_| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
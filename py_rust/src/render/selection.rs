@@ -0,0 +1,77 @@
+use std::path::{Path, PathBuf};
+
+use ignore::overrides::{Override, OverrideBuilder};
+
+use crate::{args::RenderCommand, prelude::*};
+
+/// The templates chosen to render with positional paths and --only globs, everything when neither are given.
+pub struct Selection {
+    /// Template files or directories containing them, relative to the root.
+    paths: Vec<PathBuf>,
+    /// Globs matched against template paths relative to the root.
+    only: Option<Override>,
+}
+
+impl Selection {
+    pub fn new(root: &Path, render_args: &RenderCommand) -> Result<Self, Report<Zerr>> {
+        let abs_root = std::path::absolute(root).change_context(Zerr::RootError)?;
+        let paths = render_args
+            .templates
+            .iter()
+            .map(|path| {
+                let abs_path = std::path::absolute(path).change_context(Zerr::RootError)?;
+                abs_path
+                    .strip_prefix(&abs_root)
+                    .map(normalize)
+                    .map_err(|_| {
+                        zerr!(
+                            Zerr::RootError,
+                            "Template path '{}' is not inside the root '{}'.",
+                            path.display(),
+                            root.display()
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let only = if render_args.only.is_empty() {
+            None
+        } else {
+            let mut builder = OverrideBuilder::new(".");
+            for glob in render_args.only.iter() {
+                builder
+                    .add(glob)
+                    .change_context(Zerr::RootError)
+                    .attach_printable_lazy(|| format!("Invalid --only glob: '{glob}'."))?;
+            }
+            Some(builder.build().change_context(Zerr::RootError)?)
+        };
+
+        Ok(Self { paths, only })
+    }
+
+    /// True when the whole root is being rendered.
+    pub fn is_all(&self) -> bool {
+        self.paths.is_empty() && self.only.is_none()
+    }
+
+    /// Whether a template, by its path relative to the root, is selected.
+    pub fn is_selected(&self, rel_path: &str) -> bool {
+        if self.is_all() {
+            return true;
+        }
+        let rel_path = Path::new(rel_path);
+        self.paths.iter().any(|path| rel_path.starts_with(path))
+            || self
+                .only
+                .as_ref()
+                .is_some_and(|only| only.matched(rel_path, false).is_whitelist())
+    }
+}
+
+/// Remove '.' components, e.g. from the root being './'.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, std::path::Component::CurDir))
+        .collect()
+}
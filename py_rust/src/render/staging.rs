@@ -0,0 +1,249 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::lockfile::file_mode;
+use crate::prelude::*;
+
+/// Suffix of the temp files outputs are staged in.
+static STAGED_SUFFIX: &str = ".zetch-staged";
+
+/// Outputs written to temp files next to their targets, only renamed into place once everything has succeeded,
+/// so a failed render never leaves a half written tree.
+///
+/// Anything not committed is cleaned up when dropped, along with any directories created for it.
+#[derive(Debug, Default)]
+pub struct Staging {
+    /// Temp files with the targets they replace.
+    writes: Vec<(PathBuf, PathBuf)>,
+    /// Permissions changes to existing outputs whose contents are unchanged.
+    modes: Vec<(PathBuf, u32)>,
+    /// Directories that didn't exist before, outermost first.
+    created_dirs: Vec<PathBuf>,
+}
+
+impl Staging {
+    /// Stage the new contents of an output, with its permissions when given.
+    pub fn write(
+        &mut self,
+        target: &Path,
+        contents: &[u8],
+        mode: Option<u32>,
+    ) -> Result<(), Report<Zerr>> {
+        // Renaming over a directory would only fail once others have been moved into place:
+        if target.is_dir() {
+            return Err(zerr!(
+                Zerr::RenderTemplateError,
+                "Output path '{}' is a directory.",
+                target.display()
+            ));
+        }
+
+        // Front matter can put outputs in directories that don't exist yet:
+        if let Some(parent) = target.parent() {
+            self.create_dirs(parent)?;
+        }
+
+        let temp = staged_path(target);
+        fs::write(&temp, contents).change_context(Zerr::InternalError)?;
+        self.writes.push((temp.clone(), target.to_path_buf()));
+
+        // Renaming replaces the target along with its permissions, so keep them when not set explicitly:
+        let mode = match mode {
+            Some(mode) => Some(mode),
+            None if target.exists() => file_mode(target)?,
+            None => None,
+        };
+        if let Some(mode) = mode {
+            set_mode(&temp, mode)?;
+        }
+        Ok(())
+    }
+
+    /// Stage a permissions change to an existing output.
+    pub fn set_mode(&mut self, target: &Path, mode: u32) {
+        self.modes.push((target.to_path_buf(), mode));
+    }
+
+    /// Move everything staged into place.
+    ///
+    /// All or nothing, if anything can't be moved the targets already replaced are put back and the rest discarded.
+    pub fn commit(&mut self) -> Result<(), Report<Zerr>> {
+        let mut undos = vec![];
+        match self.apply(&mut undos) {
+            Ok(()) => {
+                for undo in undos {
+                    if let Undo::Write {
+                        backup: Some(backup),
+                        ..
+                    } = undo
+                    {
+                        let _ = fs::remove_file(backup);
+                    }
+                }
+                self.writes.clear();
+                self.modes.clear();
+                self.created_dirs.clear();
+                Ok(())
+            }
+            Err(e) => {
+                for undo in undos.into_iter().rev() {
+                    undo.revert();
+                }
+                self.discard();
+                Err(e)
+            }
+        }
+    }
+
+    /// Replace each target, recording how to put back each change made.
+    fn apply(&self, undos: &mut Vec<Undo>) -> Result<(), Report<Zerr>> {
+        for (temp, target) in self.writes.iter() {
+            let failed = || {
+                format!(
+                    "Failed to move staged output into place at '{}'.",
+                    target.display()
+                )
+            };
+            // Linked rather than moved aside, so the target is always there for anything reading it:
+            let backup = if target.exists() {
+                let backup = backup_path(target);
+                fs::hard_link(target, &backup)
+                    .or_else(|_| fs::copy(target, &backup).map(|_| ()))
+                    .change_context(Zerr::InternalError)
+                    .attach_printable_lazy(failed)?;
+                Some(backup)
+            } else {
+                None
+            };
+            undos.push(Undo::Write {
+                target: target.clone(),
+                backup,
+            });
+            fs::rename(temp, target)
+                .change_context(Zerr::InternalError)
+                .attach_printable_lazy(failed)?;
+        }
+        for (target, mode) in self.modes.iter() {
+            if let Some(previous) = file_mode(target)? {
+                undos.push(Undo::Mode {
+                    target: target.clone(),
+                    mode: previous,
+                });
+            }
+            set_mode(target, *mode)?;
+        }
+        Ok(())
+    }
+
+    /// Remove everything staged, the targets are left as they were.
+    pub fn discard(&mut self) {
+        for (temp, _) in std::mem::take(&mut self.writes) {
+            let _ = fs::remove_file(temp);
+        }
+        self.modes.clear();
+        // Only removed when empty, innermost first:
+        for dir in std::mem::take(&mut self.created_dirs).iter().rev() {
+            let _ = fs::remove_dir(dir);
+        }
+    }
+
+    fn create_dirs(&mut self, dir: &Path) -> Result<(), Report<Zerr>> {
+        let missing = dir
+            .ancestors()
+            .take_while(|ancestor| !ancestor.as_os_str().is_empty() && !ancestor.exists())
+            .map(Path::to_path_buf)
+            .collect::<Vec<_>>();
+        fs::create_dir_all(dir).change_context(Zerr::InternalError)?;
+        self.created_dirs.extend(missing.into_iter().rev());
+        Ok(())
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        self.discard();
+    }
+}
+
+/// A change made by committing, with what's needed to put it back.
+#[derive(Debug)]
+enum Undo {
+    /// A target replaced by its staged contents, the backup being its previous contents when it existed.
+    Write {
+        target: PathBuf,
+        backup: Option<PathBuf>,
+    },
+    /// A target's permissions changed, from the mode given.
+    Mode { target: PathBuf, mode: u32 },
+}
+
+impl Undo {
+    /// Best effort, a failure here can't be recovered from any better.
+    fn revert(self) {
+        match self {
+            Self::Write {
+                target,
+                backup: Some(backup),
+            } => {
+                let _ = fs::rename(backup, target);
+            }
+            Self::Write {
+                target,
+                backup: None,
+            } => {
+                let _ = fs::remove_file(target);
+            }
+            Self::Mode { target, mode } => {
+                let _ = set_mode(&target, mode);
+            }
+        }
+    }
+}
+
+/// Whether the path is a temp file from staging, which watchers should ignore.
+pub fn is_staged(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(STAGED_SUFFIX)
+}
+
+/// Hidden and next to the target, so on the same filesystem for the rename to be atomic.
+pub fn staged_path(target: &Path) -> PathBuf {
+    hidden_path(target, "")
+}
+
+/// The previous contents of a target whilst committing, staged too so watchers ignore it.
+fn backup_path(target: &Path) -> PathBuf {
+    hidden_path(target, ".backup")
+}
+
+fn hidden_path(target: &Path, kind: &str) -> PathBuf {
+    let file_name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    target.with_file_name(format!(
+        ".{}.{}{}{}",
+        file_name,
+        std::process::id(),
+        kind,
+        STAGED_SUFFIX
+    ))
+}
+
+/// Set the permissions of a file, a no-op on platforms without unix modes.
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<(), Report<Zerr>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if file_mode(path)? != Some(mode) {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+            .change_context(Zerr::InternalError)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> Result<(), Report<Zerr>> {
+    Ok(())
+}
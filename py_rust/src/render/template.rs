@@ -1,10 +1,27 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug)]
+use super::front_matter::FrontMatter;
+use crate::prelude::*;
+
+/// Separates the template path from the output path in the lockfile keys of templates rendered once per foreach item.
+static FOREACH_KEY_SEP: &str = " -> ";
+
+#[derive(Clone, Debug)]
 pub struct Template {
     pub path: PathBuf,
     pub rel_path: String,
     pub out_path: PathBuf,
+    pub out_rel_path: String,
+    /// An ordinary file containing templated regions, rendered in place so the output is the file itself.
+    pub regions: bool,
+    /// A file inside a template directory that's copied to its output as it is rather than rendered, e.g. an image.
+    pub verbatim: bool,
+    /// Metadata from the top of the template, empty when it has none.
+    pub front: FrontMatter,
+    /// The foreach item this output is rendered with, None for normal templates.
+    pub item: Option<minijinja::Value>,
+    /// Identifies the template's entries in the lockfile, unique per output.
+    pub key: String,
 }
 
 impl Template {
@@ -16,8 +33,93 @@ impl Template {
                 .expect("Template path not relative to root")
                 .to_string_lossy()
                 .to_string(),
+            out_rel_path: out_path
+                .strip_prefix(&root)
+                .expect("Template output path not relative to root")
+                .to_string_lossy()
+                .to_string(),
             path,
             out_path,
+            regions: false,
+            verbatim: false,
+            front: FrontMatter::default(),
+            item: None,
+            key: String::new(),
+        }
+        .with_key()
+    }
+
+    /// A template to render with one of its foreach items, to the given output.
+    pub fn for_item(&self, root: &Path, out_path: PathBuf, item: minijinja::Value) -> Self {
+        Self {
+            item: Some(item),
+            ..self.clone()
+        }
+        .with_out_path(root, out_path)
+    }
+
+    /// The same template rendering to a different output.
+    pub fn with_out_path(self, root: &Path, out_path: PathBuf) -> Self {
+        Self {
+            out_rel_path: out_path
+                .strip_prefix(root)
+                .expect("Template output path not relative to root")
+                .to_string_lossy()
+                .to_string(),
+            out_path,
+            ..self
         }
+        .with_key()
     }
+
+    /// The variables local to this template, taking precedence over the global context.
+    pub fn context(&self) -> minijinja::Value {
+        let front = minijinja::Value::from_serialize(&self.front.context);
+        match self.item.as_ref() {
+            Some(item) => minijinja::context! { item => item, ..front },
+            None => front,
+        }
+    }
+
+    fn with_key(self) -> Self {
+        Self {
+            key: match self.item {
+                Some(_) => format!("{}{}{}", self.rel_path, FOREACH_KEY_SEP, self.out_rel_path),
+                None => self.rel_path.clone(),
+            },
+            ..self
+        }
+    }
+
+    /// Read the template's front matter, applying its output path override if it has one.
+    pub fn load_front_matter(self, root: &Path) -> Result<Self, Report<Zerr>> {
+        let front = FrontMatter::load(&self.path)?;
+        let template = match front.out_path(root, &self.path)? {
+            Some(out_path) => self.with_out_path(root, out_path),
+            None => self,
+        };
+        Ok(Self { front, ..template })
+    }
+
+    /// An ordinary file with templated regions, rendered in place.
+    pub fn new_regions(root: PathBuf, path: PathBuf) -> Self {
+        Self {
+            regions: true,
+            ..Self::new(root, path.clone(), path)
+        }
+    }
+
+    /// A file inside a template directory copied to its output as it is, it can't have front matter.
+    pub fn new_verbatim(root: PathBuf, path: PathBuf, out_path: PathBuf) -> Self {
+        Self {
+            verbatim: true,
+            ..Self::new(root, path, out_path)
+        }
+    }
+}
+
+/// The path of the template a lockfile key belongs to, relative to the root.
+pub fn key_template_path(key: &str) -> &str {
+    key.split_once(FOREACH_KEY_SEP)
+        .map_or(key, |(path, _)| path)
 }
@@ -1,10 +1,16 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
 
-use ignore::{overrides::OverrideBuilder, WalkBuilder};
-use regex::Regex;
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    overrides::{Override, OverrideBuilder},
+    WalkBuilder,
+};
 use tracing::debug;
 
-use super::lockfile::LOCKFILE_NAME;
+use super::{lockfile::LOCKFILE_NAME, matching::Matching, template::Template};
 use crate::{prelude::*, state::State};
 
 pub fn create(root: &Path, state: &State) -> Result<WalkBuilder, Report<Zerr>> {
@@ -20,21 +26,8 @@ pub fn create(root: &Path, state: &State) -> Result<WalkBuilder, Report<Zerr>> {
         builder.add_ignore(ignore_file);
     }
 
-    let mut all_excludes = vec![
-        // Don't ever match the lockfile:
-        LOCKFILE_NAME.to_string(),
-    ];
-
-    // If the config is inside the root, add it to the excludes:
-    if let Some(rel_config) = config_path_relative_to_root(root, &state.final_config_path)? {
-        all_excludes.push(rel_config.display().to_string());
-    }
-
-    // Add in config supplied excludes:
-    all_excludes.extend(state.conf.exclude.iter().map(|s| s.to_string()));
-
     let mut overrider: OverrideBuilder = OverrideBuilder::new(root);
-    for exclude in all_excludes.iter() {
+    for exclude in all_excludes(root, state)?.iter() {
         // The override adder is the opposite, i.e. a match is a whitelist, so need to invert the exclude pattern provided:
         let trimmed = exclude.trim();
         let inverted = if trimmed.starts_with('!') {
@@ -63,11 +56,48 @@ pub fn create(root: &Path, state: &State) -> Result<WalkBuilder, Report<Zerr>> {
     Ok(builder)
 }
 
-/// If config is inside root, return the relative path to it, otherwise return None.
-fn config_path_relative_to_root(
-    root: &Path,
-    config: &Path,
-) -> Result<Option<PathBuf>, Report<Zerr>> {
+/// Everything left out of the template search apart from ignore files, as git-style patterns.
+fn all_excludes(root: &Path, state: &State) -> Result<Vec<String>, Report<Zerr>> {
+    let mut all_excludes = vec![
+        // Don't ever match the lockfile:
+        LOCKFILE_NAME.to_string(),
+    ];
+
+    // If the config is inside the root, add it to the excludes:
+    if let Some(rel_config) = path_relative_to_root(root, &state.final_config_path)? {
+        all_excludes.push(rel_config.display().to_string());
+    }
+
+    // Template dirs hold shared includes and macros, never rendered standalone:
+    for template_dir in state.conf.engine.template_dirs.iter() {
+        if let Some(rel_dir) = path_relative_to_root(root, Path::new(template_dir))?
+            .filter(|rel_dir| !rel_dir.as_os_str().is_empty())
+        {
+            all_excludes.push(format!("/{}/", rel_dir.display()));
+        }
+    }
+
+    // Add in config supplied excludes:
+    all_excludes.extend(state.conf.exclude.iter().map(|s| s.to_string()));
+
+    Ok(all_excludes)
+}
+
+/// Matches paths left out of the template search by the excludes, to stop outputs being written over them.
+///
+/// Ignore files aren't included, outputs are often git ignored.
+pub fn excludes(root: &Path, state: &State) -> Result<Gitignore, Report<Zerr>> {
+    let mut builder = GitignoreBuilder::new(root);
+    for exclude in all_excludes(root, state)? {
+        builder
+            .add_line(None, exclude.trim())
+            .change_context(Zerr::InternalError)?;
+    }
+    builder.build().change_context(Zerr::InternalError)
+}
+
+/// If the path (e.g. the config) is inside root, return the relative path to it, otherwise return None.
+fn path_relative_to_root(root: &Path, path: &Path) -> Result<Option<PathBuf>, Report<Zerr>> {
     // Make both absolute to start:
     let root = if root.is_relative() {
         root.canonicalize().change_context(Zerr::InternalError)?
@@ -75,17 +105,16 @@ fn config_path_relative_to_root(
         root.to_path_buf()
     };
 
-    let config = if config.is_relative() {
-        config.canonicalize().change_context(Zerr::InternalError)?
+    let path = if path.is_relative() {
+        path.canonicalize().change_context(Zerr::InternalError)?
     } else {
-        config.to_path_buf()
+        path.to_path_buf()
     };
 
-    // If config is inside root, return the relative path to it, otherwise return None.
-    if config.starts_with(&root) {
+    // If path is inside root, return the relative path to it, otherwise return None.
+    if path.starts_with(&root) {
         Ok(Some(
-            config
-                .strip_prefix(&root)
+            path.strip_prefix(&root)
                 .change_context(Zerr::InternalError)?
                 .to_path_buf(),
         ))
@@ -94,110 +123,185 @@ fn config_path_relative_to_root(
     }
 }
 
-fn get_middle_regex(matcher: &str) -> Regex {
-    Regex::new(&format!(r"(.*)(\.{matcher}\.)(.*)")).expect("Regex failed to compile")
-}
-
-fn get_end_regex(matcher: &str) -> Regex {
-    Regex::new(&format!(r"(.*)(\.{matcher})$")).expect("Regex failed to compile")
+/// The output path when the file is a template, with the matcher removed from its name or renamed by its match rule.
+///
+/// Every file inside a matched directory is a template too, the directory also renamed in the output path.
+fn template_out_path(root: &Path, path: &Path, matching: &Matching) -> Option<PathBuf> {
+    let rel_path = path.strip_prefix(root).ok()?;
+    let mut out_path = root.to_path_buf();
+    let mut matched = false;
+    for component in rel_path.components() {
+        // Don't match twice with different matchers:
+        match matching.out_name(&component.as_os_str().to_string_lossy()) {
+            Some(out_name) => {
+                out_path.push(out_name);
+                matched = true;
+            }
+            None => out_path.push(component),
+        }
+    }
+    matched.then_some(out_path)
 }
 
-fn try_regexes_and_rewrite(
-    filename: &str,
-    middle_regex: &Regex,
-    end_regex: &Regex,
-) -> Option<String> {
-    if let Some(caps) = middle_regex.captures(filename) {
-        return Some(format!(
-            "{}.{}",
-            caps.get(1).map_or("", |m| m.as_str()),
-            caps.get(3).map_or("", |m| m.as_str())
-        ));
+/// Whether a template is a file inside a template directory that should be copied as it is,
+/// either matching the verbatim globs or not utf-8 so it can't be rendered, e.g. an image.
+fn is_verbatim(
+    root: &Path,
+    path: &Path,
+    matching: &Matching,
+    verbatim: &Override,
+) -> Result<bool, Report<Zerr>> {
+    // Files matched by name are always rendered:
+    let in_dir = path
+        .file_name()
+        .is_some_and(|name| matching.out_name(&name.to_string_lossy()).is_none());
+    if !in_dir {
+        return Ok(false);
     }
-
-    if let Some(caps) = end_regex.captures(filename) {
-        return Some(caps.get(1).map_or("", |m| m.as_str()).to_string());
+    if path
+        .strip_prefix(root)
+        .is_ok_and(|rel_path| verbatim.matched(rel_path, false).is_whitelist())
+    {
+        return Ok(true);
     }
-
-    None
+    let contents = std::fs::read(path).change_context(Zerr::InternalError)?;
+    Ok(std::str::from_utf8(&contents).is_err())
 }
 
+/// Find all templates, along with ordinary files matching the regions globs that contain templated regions.
+///
+/// Files inside template directories matching the verbatim globs, or that aren't utf-8, are copied rather than rendered.
+///
+/// Fails when the outputs conflict, see check_outputs().
 pub fn find_templates(
     root: &Path,
     walker: WalkBuilder,
-    matchers: &[String],
-) -> Result<Vec<super::template::Template>, Report<Zerr>> {
-    let regex_pairs = matchers
-        .iter()
-        .map(|matcher| {
-            let middle_regex = get_middle_regex(matcher);
-            let end_regex = get_end_regex(matcher);
-            (middle_regex, end_regex)
-        })
-        .collect::<Vec<_>>();
-
+    excludes: &Gitignore,
+    matching: &Matching,
+    regions: Option<&Override>,
+    verbatim: &Override,
+) -> Result<Vec<Template>, Report<Zerr>> {
     let mut templates = vec![];
+    // Ordinary files that might contain templated regions:
+    let mut maybe_regions = vec![];
     let mut files_checked = 0;
     for entry in walker.build() {
         let entry = entry.change_context(Zerr::InternalError)?;
         if entry.file_type().map(|ft| ft.is_file()).unwrap_or(false) {
-            let filename = entry.file_name().to_string_lossy();
-            for (middle_regex, end_regex) in regex_pairs.iter() {
-                if let Some(compiled_name) =
-                    try_regexes_and_rewrite(&filename, middle_regex, end_regex)
-                {
-                    templates.push(super::template::Template::new(
-                        root.into(),
-                        entry.path().to_path_buf(),
-                        // Replacing the name with the compiled name:
-                        entry.path().parent().unwrap().join(compiled_name),
-                    ));
-                    // Don't match twice with different matchers:
-                    break;
-                }
+            if let Some(out_path) = template_out_path(root, entry.path(), matching) {
+                templates.push(if is_verbatim(root, entry.path(), matching, verbatim)? {
+                    Template::new_verbatim(root.into(), entry.path().to_path_buf(), out_path)
+                } else {
+                    Template::new(root.into(), entry.path().to_path_buf(), out_path)
+                        .load_front_matter(root)?
+                });
+            } else if regions.is_some_and(|regions| {
+                entry
+                    .path()
+                    .strip_prefix(root)
+                    .is_ok_and(|rel_path| regions.matched(rel_path, false).is_whitelist())
+            }) {
+                maybe_regions.push(entry.into_path());
             }
         }
         files_checked += 1;
     }
 
+    check_outputs(&templates, excludes)?;
+
+    // Outputs of templates are managed by those templates, so never treated as having regions:
+    let outputs = templates
+        .iter()
+        .map(|t| t.out_path.clone())
+        .collect::<HashSet<_>>();
+    let mut num_regions = 0;
+    for path in maybe_regions {
+        if outputs.contains(&path) {
+            continue;
+        }
+        // Binary or unreadable files can't contain regions:
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        if super::regions::has_markers(&contents) {
+            templates.push(Template::new_regions(root.into(), path));
+            num_regions += 1;
+        }
+    }
+
     debug!(
-        "Checked {} unignored files to find {} templates.",
+        "Checked {} unignored files to find {} templates, {} of which contain templated regions.",
         files_checked,
-        templates.len()
+        templates.len(),
+        num_regions
     );
 
     Ok(templates)
 }
 
-/// Replace the matcher in the filename with the new matcher.
-/// Used by the replace-matcher command.
-fn rewrite_template_matcher(
-    filename: &str,
-    middle_regex: &Regex,
-    end_regex: &Regex,
-    new_matcher: &str,
-) -> Result<String, Report<Zerr>> {
-    let filename = if let Some(caps) = middle_regex.captures(filename) {
-        format!(
-            "{}.{}.{}",
-            caps.get(1).map_or("", |m| m.as_str()),
-            new_matcher,
-            caps.get(3).map_or("", |m| m.as_str())
-        )
-    } else {
-        filename.to_string()
-    };
+/// Make sure no two templates render to the same output, and no output lands on a template or an excluded file.
+///
+/// Every conflict is reported at once. Outputs of foreach templates aren't known until expanded, so are checked again after.
+pub fn check_outputs(templates: &[Template], excludes: &Gitignore) -> Result<(), Report<Zerr>> {
+    let template_paths = templates
+        .iter()
+        .map(|t| t.path.as_path())
+        .collect::<HashSet<_>>();
 
-    let filename = if let Some(caps) = end_regex.captures(&filename) {
-        format!("{}.{}", caps.get(1).map_or("", |m| m.as_str()), new_matcher)
-    } else {
-        filename
-    };
+    // Files with templated regions are their own output, sorted so conflicts are reported in a stable order:
+    let mut checked = templates
+        .iter()
+        .filter(|t| !t.regions && (t.front.foreach.is_none() || t.item.is_some()))
+        .collect::<Vec<_>>();
+    checked.sort_by(|a, b| a.key.cmp(&b.key));
+
+    let mut by_output: HashMap<&Path, &Template> = HashMap::new();
+    let mut conflicts = vec![];
+    for template in checked {
+        match by_output.entry(template.out_path.as_path()) {
+            Entry::Occupied(first) => conflicts.push(format!(
+                "'{}' and '{}' both render to '{}'.",
+                first.get().rel_path,
+                template.rel_path,
+                template.out_rel_path
+            )),
+            Entry::Vacant(entry) => {
+                entry.insert(template);
+            }
+        }
+        if template_paths.contains(template.out_path.as_path()) {
+            conflicts.push(format!(
+                "'{}' renders to '{}', which is itself a template.",
+                template.rel_path, template.out_rel_path
+            ));
+        } else if excludes
+            .matched_path_or_any_parents(&template.out_path, false)
+            .is_ignore()
+        {
+            conflicts.push(format!(
+                "'{}' renders to '{}', which is excluded.",
+                template.rel_path, template.out_rel_path
+            ));
+        }
+    }
+
+    if conflicts.is_empty() {
+        return Ok(());
+    }
 
-    Ok(filename)
+    let mut report = zerr!(
+        Zerr::RenderTemplateError,
+        "Found {} conflicting template output{}, each output needs its own path that isn't a template or excluded:",
+        conflicts.len(),
+        if conflicts.len() == 1 { "" } else { "s" }
+    );
+    for conflict in conflicts {
+        report = report.attach_printable(conflict);
+    }
+    Err(report)
 }
 
-/// Returns a mapping of current template paths to new template paths with an old and new match string.
+/// Returns a mapping of current template paths to new template paths with an old and new matcher, or match_rules pattern.
 /// Used by the replace-matcher command, otherwise used internally in render().
 pub fn get_template_matcher_rewrite_mapping(
     root: &Path,
@@ -205,37 +309,37 @@ pub fn get_template_matcher_rewrite_mapping(
     old_matcher: &str,
     new_matcher: &str,
 ) -> Result<Vec<(PathBuf, PathBuf)>, Report<Zerr>> {
-    let templates = find_templates(root, create(root, state)?, &[old_matcher.to_string()])?;
+    let old_matching = Matching::single(&state.conf, old_matcher)?;
+    let new_matching = Matching::single(&state.conf, new_matcher)?;
+    let templates = find_templates(
+        root,
+        create(root, state)?,
+        &excludes(root, state)?,
+        &old_matching,
+        None,
+        &Override::empty(),
+    )?;
 
-    let middle_regex = get_middle_regex(old_matcher);
-    let end_regex = get_end_regex(old_matcher);
+    let mut mapping = vec![];
+    for t in templates {
+        let old_filename = t
+            .path
+            .file_name()
+            .ok_or_else(|| {
+                zerr!(
+                    Zerr::InternalError,
+                    "Failed to get filename from path: {}",
+                    t.path.display()
+                )
+            })?
+            .to_string_lossy()
+            .to_string();
 
-    templates
-        .into_iter()
-        .map(|t| {
-            let old_filename = t
-                .path
-                .file_name()
-                .ok_or_else(|| {
-                    zerr!(
-                        Zerr::InternalError,
-                        "Failed to get filename from path: {}",
-                        t.path.display()
-                    )
-                })?
-                .to_string_lossy()
-                .to_string();
-
-            let new_path = t
-                .path
-                .to_path_buf()
-                .with_file_name(rewrite_template_matcher(
-                    &old_filename,
-                    &middle_regex,
-                    &end_regex,
-                    new_matcher,
-                )?);
-            Ok((t.path, new_path))
-        })
-        .collect::<Result<Vec<_>, Report<Zerr>>>()
+        // Files inside template directories needn't carry the matcher themselves:
+        if let Some(new_filename) = old_matching.rename(&old_filename, &new_matching)? {
+            let new_path = t.path.with_file_name(new_filename);
+            mapping.push((t.path, new_path));
+        }
+    }
+    Ok(mapping)
 }
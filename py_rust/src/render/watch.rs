@@ -0,0 +1,432 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use notify::{RecursiveMode, Watcher};
+use pyo3::prelude::*;
+
+use super::{
+    deps::Tracker,
+    find_templates,
+    loader::SearchPaths,
+    lockfile::{Lockfile, LOCKFILE_NAME},
+    mini_env::new_mini_env,
+    print_summary, render_templates, select_templates,
+    selection::Selection,
+    staging::is_staged,
+    template::Template,
+    Rendered, Status,
+};
+use crate::{args::WatchCommand, custom_exts::py_interface, prelude::*, state::State};
+
+/// How often to check for Ctrl-C whilst waiting for filesystem changes.
+static SIGNAL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+type Events = Receiver<notify::Result<notify::Event>>;
+
+/// Render all templates, then keep watching for changes, re-rendering affected templates until interrupted.
+///
+/// - Changes to templates re-render just those templates.
+/// - Changes to other files in the root or template dirs may be includes, so check all templates, those whose inputs are unchanged are skipped.
+/// - Changes to the config, ignore files, custom extensions or preloads reload the whole state and environment.
+pub fn watch(args: &crate::args::Args, watch_args: &WatchCommand) -> Result<(), Report<Zerr>> {
+    let render_args = &watch_args.render;
+    super::args_validate::args_validate(render_args)?;
+
+    if render_args.check {
+        return Err(zerr!(
+            Zerr::WatchError,
+            "--check can't be used with watch, use 'zetch render --check' instead."
+        ));
+    }
+    if render_args.report.is_some() {
+        return Err(zerr!(
+            Zerr::WatchError,
+            "--report can't be used with watch, use 'zetch render --report' instead."
+        ));
+    }
+
+    let root = render_args
+        .root
+        .canonicalize()
+        .change_context(Zerr::RootError)?;
+    // Templates are found under the canonical root, so everything else needs to use it too:
+    let mut watch_args = watch_args.clone();
+    watch_args.render.root = root.clone();
+    let debounce = Duration::from_millis(watch_args.debounce);
+
+    let (tx, rx) = channel();
+    let mut watcher = notify::recommended_watcher(tx).change_context(Zerr::WatchError)?;
+    watcher
+        .watch(&root, RecursiveMode::Recursive)
+        .change_context(Zerr::WatchError)?;
+
+    // Files outside the root that are being watched (config, ignore files, extensions):
+    let mut watched_extras = HashSet::new();
+    // Make sure the config is watched even if the first load fails, so fixing it triggers a reload:
+    if let Some(config) = guess_config_path(args, &root) {
+        watch_extra(&mut watcher, &mut watched_extras, &root, &config)?;
+    }
+
+    info!(
+        "Watching '{}' for changes, press Ctrl-C to stop.",
+        root.display()
+    );
+
+    let mut force = render_args.force;
+    loop {
+        let result = watch_state(
+            args,
+            &watch_args,
+            &root,
+            &rx,
+            debounce,
+            force,
+            &mut watcher,
+            &mut watched_extras,
+        );
+        force = false;
+
+        match result {
+            // Needs a full reload:
+            Ok(true) => continue,
+            // Interrupted:
+            Ok(false) => return Ok(()),
+            Err(e) => {
+                // Don't exit on user errors, wait for them to be fixed then try reloading:
+                if matches!(e.current_context(), Zerr::InternalError | Zerr::WatchError) {
+                    return Err(e);
+                }
+                error!("{e:?}");
+                info!("Waiting for changes...");
+                if wait_for_changes(&rx, debounce)?.is_none() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Load the state and environment, render everything, then incrementally re-render on changes.
+///
+/// Returns true when a full reload is needed, false when interrupted.
+#[allow(clippy::too_many_arguments)]
+fn watch_state(
+    args: &crate::args::Args,
+    watch_args: &WatchCommand,
+    root: &Path,
+    rx: &Events,
+    debounce: Duration,
+    force: bool,
+    watcher: &mut notify::RecommendedWatcher,
+    watched_extras: &mut HashSet<PathBuf>,
+) -> Result<bool, Report<Zerr>> {
+    let start = Instant::now();
+
+    let mut state = State::new(args)?;
+    state.load_all_vars()?;
+
+    // Anything changing these requires a full reload:
+    let mut reload_paths = vec![canonical(&state.final_config_path)];
+    for ignore_file in state.conf.ignore_files.iter() {
+        reload_paths.push(canonical(Path::new(ignore_file)));
+    }
+    for extension in state.conf.engine.custom_extensions.iter() {
+        reload_paths.push(canonical(Path::new(extension)));
+    }
+    // Preloads are evaluated once when the environment's created:
+    let search = SearchPaths::new(root, &state);
+    for preload in state.conf.engine.preload.iter() {
+        if let Some(path) = search.resolve(&preload.template) {
+            reload_paths.push(canonical(&path));
+        }
+    }
+    for path in reload_paths.iter() {
+        watch_extra(watcher, watched_extras, root, path)?;
+    }
+    // Shared includes can live outside the root:
+    let template_dirs = state
+        .conf
+        .engine
+        .template_dirs
+        .iter()
+        .map(|dir| canonical(Path::new(dir)))
+        .collect::<Vec<_>>();
+    for dir in template_dirs.iter() {
+        watch_extra(watcher, watched_extras, root, dir)?;
+    }
+
+    // The extensions need resetting before the next reload, whether this load succeeds or not:
+    let result = (|| {
+        let mut tracker = Tracker::new(root, &state)?;
+        let mut env = new_mini_env(root, &state, &mut tracker)?;
+
+        let num_pre_tasks = if state.light {
+            0
+        } else {
+            state.conf.tasks.pre.len()
+        };
+        let mut outputs = render_cycle(
+            &mut env,
+            &tracker,
+            &state,
+            watch_args,
+            root,
+            None,
+            force,
+            num_pre_tasks,
+            start,
+        )?;
+
+        loop {
+            let changed = match wait_for_changes(rx, debounce)? {
+                Some(changed) => changed,
+                None => return Ok(false),
+            };
+            let start = Instant::now();
+
+            let lockfile_path = root.join(LOCKFILE_NAME);
+            let changed = changed
+                .into_iter()
+                .filter(|path| {
+                    // Ignore zetch's own writes, and git internals:
+                    path != &lockfile_path
+                        && !outputs.contains(path)
+                        && !is_staged(path)
+                        && !path.components().any(|c| c.as_os_str() == ".git")
+                })
+                .collect::<HashSet<_>>();
+
+            if changed
+                .iter()
+                .any(|path| reload_paths.iter().any(|reload| path.starts_with(reload)))
+            {
+                info!("Config or extensions changed, reloading.");
+                return Ok(true);
+            }
+
+            // Only react to changes inside the root or template dirs from here on:
+            let changed = changed
+                .into_iter()
+                .filter(|path| {
+                    path.starts_with(root) || template_dirs.iter().any(|dir| path.starts_with(dir))
+                })
+                .collect::<HashSet<_>>();
+            if changed.is_empty() {
+                continue;
+            }
+
+            outputs = match render_cycle(
+                &mut env,
+                &tracker,
+                &state,
+                watch_args,
+                root,
+                Some(&changed),
+                false,
+                0,
+                start,
+            ) {
+                Ok(outputs) => outputs,
+                Err(e) => {
+                    // Template errors shouldn't stop the watcher, print and wait for the next change:
+                    if matches!(e.current_context(), Zerr::InternalError) {
+                        return Err(e);
+                    }
+                    error!("{e:?}");
+                    outputs
+                }
+            };
+        }
+    })();
+
+    py_interface::unload_custom_exts(&state.conf.engine.custom_extensions)?;
+
+    result
+}
+
+/// Render the templates affected by the changed paths (all when None) and sync the lockfile.
+///
+/// Returns the output paths of all current templates and any deleted orphans, so zetch's own changes can be ignored by the watcher.
+#[allow(clippy::too_many_arguments)]
+fn render_cycle(
+    env: &mut minijinja::Environment,
+    tracker: &Tracker,
+    state: &State,
+    watch_args: &WatchCommand,
+    root: &Path,
+    changed: Option<&HashSet<PathBuf>>,
+    force: bool,
+    num_pre_tasks: usize,
+    start: Instant,
+) -> Result<HashSet<PathBuf>, Report<Zerr>> {
+    // Templates may have been modified since last loaded:
+    env.clear_templates();
+
+    let mut lockfile = Lockfile::load(root.to_path_buf(), force);
+    let templates = select_templates(
+        find_templates(root, state)?,
+        &Selection::new(root, &watch_args.render)?,
+        &mut lockfile,
+    );
+    // Files with templated regions are also hand edited, zetch's own writes to them are skipped as unchanged next cycle:
+    let mut outputs = templates
+        .iter()
+        .filter(|t| !t.regions)
+        .map(|t| t.out_path.clone())
+        .collect::<HashSet<_>>();
+
+    let to_render = if let Some(changed) = changed {
+        let template_paths = templates
+            .iter()
+            .map(|t| t.path.clone())
+            .collect::<HashSet<_>>();
+        // Any other change might be a dependency of any template, the lockfile's dependency tracking will skip the unaffected:
+        let only_templates_changed = changed
+            .iter()
+            .all(|path| template_paths.contains(path) || !path.exists());
+
+        let (to_render, to_keep): (Vec<Template>, Vec<Template>) =
+            templates.into_iter().partition(|t| {
+                !only_templates_changed
+                    || changed.contains(&t.path)
+                    || lockfile
+                        .deps(t)
+                        .is_some_and(|deps| tracker.includes_changed(deps, changed))
+            });
+        for template in to_keep.iter() {
+            lockfile.keep_template(template);
+        }
+        to_render
+    } else {
+        templates
+    };
+
+    let mut rendered = Rendered::default();
+    render_templates(
+        env,
+        tracker,
+        state,
+        to_render,
+        &watch_args.render,
+        &mut lockfile,
+        &mut rendered,
+    )?;
+
+    // Foreach templates are only expanded to their outputs when rendered:
+    outputs.extend(
+        rendered
+            .templates
+            .iter()
+            .filter(|handled| !handled.template.regions)
+            .map(|handled| handled.template.out_path.clone()),
+    );
+    // Synced before post tasks, so it matches the outputs even if one fails:
+    rendered.orphans_deleted = lockfile.sync(state.conf.orphans)?;
+    outputs.extend(rendered.orphans_deleted.iter().map(|out| root.join(out)));
+
+    // Post tasks only need running when something actually changed:
+    let num_post_tasks = if !state.light && rendered.count(Status::Written) > 0 {
+        state.conf.tasks.run_post(state)?;
+        lockfile.record_post_task_changes()?;
+        state.conf.tasks.post.len()
+    } else {
+        0
+    };
+
+    print_summary(
+        &rendered,
+        num_pre_tasks + num_post_tasks,
+        lockfile.modified,
+        start.elapsed(),
+    );
+
+    Ok(outputs)
+}
+
+/// Block until some filesystem changes come in, then keep collecting until quiet for the debounce period.
+///
+/// Returns None if interrupted with Ctrl-C.
+fn wait_for_changes(
+    rx: &Events,
+    debounce: Duration,
+) -> Result<Option<HashSet<PathBuf>>, Report<Zerr>> {
+    let mut changed = HashSet::new();
+    loop {
+        // Python handles SIGINT, so need to check manually as otherwise would block forever:
+        if Python::with_gil(|py| py.check_signals()).is_err() {
+            return Ok(None);
+        }
+
+        let timeout = if changed.is_empty() {
+            SIGNAL_CHECK_INTERVAL
+        } else {
+            debounce
+        };
+        match rx.recv_timeout(timeout) {
+            Ok(event) => {
+                let event = event.change_context(Zerr::WatchError)?;
+                if !matches!(event.kind, notify::EventKind::Access(_)) {
+                    changed.extend(event.paths);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                if !changed.is_empty() {
+                    return Ok(Some(changed));
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(zerr!(Zerr::WatchError, "Filesystem watcher disconnected."));
+            }
+        }
+    }
+}
+
+/// Watch a path outside the root, paths inside are already covered by the recursive root watch.
+///
+/// Files are watched via their parent directory, as editors often save by replacing the file.
+fn watch_extra(
+    watcher: &mut notify::RecommendedWatcher,
+    watched_extras: &mut HashSet<PathBuf>,
+    root: &Path,
+    path: &Path,
+) -> Result<(), Report<Zerr>> {
+    if path.starts_with(root) {
+        return Ok(());
+    }
+
+    let (target, mode) = if path.is_dir() {
+        (path.to_path_buf(), RecursiveMode::Recursive)
+    } else if let Some(parent) = path.parent() {
+        (parent.to_path_buf(), RecursiveMode::NonRecursive)
+    } else {
+        return Ok(());
+    };
+
+    if watched_extras.insert(target.clone()) {
+        watcher
+            .watch(&target, mode)
+            .change_context(Zerr::WatchError)
+            .attach_printable_lazy(|| format!("Failed to watch '{}'.", target.display()))?;
+    }
+    Ok(())
+}
+
+/// Best effort location of the config before it's loaded, mirroring the search done when creating the state.
+fn guess_config_path(args: &crate::args::Args, root: &Path) -> Option<PathBuf> {
+    if args.config.exists() {
+        Some(canonical(&args.config))
+    } else if args.config.is_relative() && root.join(&args.config).exists() {
+        Some(canonical(&root.join(&args.config)))
+    } else {
+        None
+    }
+}
+
+/// Canonicalize to match the absolute paths from watcher events, falling back to the original.
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
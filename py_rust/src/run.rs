@@ -38,7 +38,10 @@ pub fn run() -> Result<(), Report<Zerr>> {
         py_args.insert(1, DEFAULT_SUBCOMMAND.into());
     }
 
-    let args = args::Args::parse_from(py_args);
+    let mut args = args::Args::parse_from(py_args);
+    if let Some(render) = args.command.render_args_mut() {
+        render.template_as_root();
+    }
 
     // Setup global logging:
     let mut builder = GlobalLog::builder();
@@ -52,14 +55,19 @@ pub fn run() -> Result<(), Report<Zerr>> {
         builder = builder
             .stdout(true, true)
             .level_from(
-                // If its read, put, delete or var subcommands, stdout is important, so only show error!() in default mode:
+                // If its read, put, delete or var subcommands, or the render report is going to stdout, stdout is important, so only show error!() in default mode:
                 if matches!(
                     &args.command,
                     args::Command::Read(_)
                         | args::Command::Put(_)
                         | args::Command::Del(_)
                         | args::Command::Var(_)
-                ) {
+                ) || args
+                    .command
+                    .render_args()
+                    .and_then(|render| render.report.as_deref())
+                    .is_some_and(|report| report == std::path::Path::new("-"))
+                {
                     tracing::Level::ERROR
                 } else {
                     tracing::Level::INFO
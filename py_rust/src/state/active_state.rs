@@ -7,7 +7,7 @@ use parking_lot::Mutex;
 use tempfile::NamedTempFile;
 
 use super::parent_state::load_parent_state;
-use crate::{args::Command, config::conf::Config, prelude::*};
+use crate::{config::conf::Config, prelude::*};
 
 #[derive(Debug)]
 pub struct State {
@@ -52,21 +52,27 @@ impl State {
         } else {
             let final_config_path = build_final_config_path(
                 &args.config,
-                if let crate::args::Command::Render(render) = &args.command {
-                    Some(&render.root)
-                } else {
-                    None
-                },
+                args.command
+                    .render_args()
+                    .map(|render| render.root.as_path()),
             )?;
 
+            // Check mode shouldn't modify anything, so the config isn't updated and pre-tasks are skipped like post-tasks:
+            let check = args
+                .command
+                .render_args()
+                .is_some_and(|render| render.check);
+
             let conf = timeit!("Config processing", {
-                Config::from_toml(&final_config_path)
+                Config::from_toml(&final_config_path, !check)
             })?;
 
             // Run the pre-tasks if applicable to the active command.
             // Note this won't be run if in child process (which makes sense), due to above return.
             let command_expecting_tasks = match &args.command {
-                crate::args::Command::Render(_) | crate::args::Command::Var(_) => true,
+                crate::args::Command::Render(_)
+                | crate::args::Command::Watch(_)
+                | crate::args::Command::Var(_) => true,
                 crate::args::Command::Read(_)
                 | crate::args::Command::Put(_)
                 | crate::args::Command::Del(_)
@@ -76,14 +82,14 @@ impl State {
             };
 
             // Set light to true if --light or --superlight, and superlight to true if --superlight:
-            let (light, superlight) = if let crate::args::Command::Render(render) = &args.command {
+            let (light, superlight) = if let Some(render) = args.command.render_args() {
                 (render.light || render.superlight, render.superlight)
             } else {
                 (false, false)
             };
 
-            // Run pre-tasks if the right type of command and not running in light/superlight mode:
-            if command_expecting_tasks && !light {
+            // Run pre-tasks if the right type of command and not running in light/superlight or check mode:
+            if command_expecting_tasks && !light && !check {
                 conf.tasks.run_pre(&final_config_path)?;
             }
 
@@ -166,9 +172,8 @@ impl State {
 
                 // Env vars:
                 // If some env defaults banned, validate list and convert to a hashset for faster lookup:
-                let banned_env_defaults: Option<HashSet<String>> = if let Command::Render(
-                    render_args,
-                ) = &self.args.command
+                let banned_env_defaults: Option<HashSet<String>> = if let Some(render_args) =
+                    self.args.command.render_args()
                 {
                     if let Some(banned) = render_args.ban_defaults.as_ref() {
                         // If no vars provided, ban all defaults:
@@ -263,7 +268,7 @@ impl State {
                         let var = var.clone();
                         handles.push(std::thread::spawn(
                             move || -> Result<(String, serde_json::Value), Report<Zerr>> {
-                                timeit!(format!("Cli var processing: '{}'", &key).as_str(), {
+                                timeit!(format!("Cli var processing: '{}'", key).as_str(), {
                                     Ok((key, var.read(&final_config_path)?))
                                 })
                             },
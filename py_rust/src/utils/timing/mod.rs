@@ -70,6 +70,18 @@ mod tests {
 
         let formatted = recorder.format_verbose().unwrap();
         assert!(formatted.contains("test"));
+
+        recorder.timeit("other", || {});
+        recorder.timeit("test", || {});
+        let durations = recorder.durations().unwrap();
+        assert_eq!(
+            durations
+                .iter()
+                .map(|(description, _)| description.as_str())
+                .collect::<Vec<_>>(),
+            vec!["test", "other"]
+        );
+        assert!(durations[0].1 >= Duration::from_millis(1));
     }
 
     #[rstest]
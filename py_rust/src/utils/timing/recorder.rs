@@ -100,6 +100,20 @@ impl TimeRecorder {
             .change_context(AnyErr)
     }
 
+    /// The recorded durations in the order they were first logged.
+    pub fn durations(&self) -> Result<Vec<(String, std::time::Duration)>, Report<AnyErr>> {
+        let logs = self
+            .logs
+            .try_lock()
+            .ok_or_else(|| Report::new(AnyErr).attach_printable("Failed to acquire logs."))?;
+
+        Ok(logs
+            .sorted_logs()
+            .into_iter()
+            .map(|(description, log)| (description.to_string(), log.duration))
+            .collect())
+    }
+
     /// Format the logs in a verbose, table format.
     pub fn format_verbose(&self) -> Result<String, Report<AnyErr>> {
         use comfy_table::*;
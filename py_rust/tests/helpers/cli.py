@@ -49,6 +49,22 @@ def render(
     }
 
 
+def watch(
+    root: tp.Union[str, pathlib.Path],
+    config_file: tp.Union[str, "os.PathLike[str]"],
+    extra_args: tp.Optional["list[str]"] = None,
+    cwd: tp.Optional[tp.Union[str, pathlib.Path]] = None,
+) -> "subprocess.Popen[str]":
+    """Start zetch watch in the background, the caller is responsible for stopping the process."""
+    args = ["zetch", "watch", str(root), "--config", str(config_file), "--debounce", "50"]
+    if extra_args is not None:
+        args += extra_args
+
+    return subprocess.Popen(
+        args, stdout=subprocess.PIPE, stderr=subprocess.STDOUT, text=True, cwd=cwd
+    )
+
+
 def init(root: tp.Union[str, pathlib.Path]):
     args = ["zetch", "init"]
     p1 = subprocess.run(args, capture_output=True, text=True, cwd=root)
@@ -22,6 +22,9 @@ class StaticCtx(tp.TypedDict):
     coerce: tp.NotRequired[Coerce_T]
 
 
+Preload = tp.TypedDict("Preload", {"template": str, "as": tp.NotRequired[str]})
+
+
 class Engine(tp.TypedDict):
     variable_start: tp.NotRequired[str]
     variable_end: tp.NotRequired[str]
@@ -30,6 +33,33 @@ class Engine(tp.TypedDict):
     comment_start: tp.NotRequired[str]
     comment_end: tp.NotRequired[str]
     custom_extensions: tp.NotRequired["list[str]"]
+    template_dirs: tp.NotRequired["list[str]"]
+    relative_includes: tp.NotRequired[bool]
+    preload: tp.NotRequired["list[Preload]"]
+    trim_blocks: tp.NotRequired[bool]
+    lstrip_blocks: tp.NotRequired[bool]
+    undefined: tp.NotRequired[tp.Literal["strict", "chainable", "lenient"]]
+    recursion_limit: tp.NotRequired[int]
+    fuel: tp.NotRequired[int]
+    overrides: tp.NotRequired["list[EngineOverride]"]
+    autoescape: tp.NotRequired[
+        "dict[str, tp.Literal['json', 'yaml-string', 'toml-string', 'shell']]"
+    ]
+
+
+class EngineOverride(tp.TypedDict):
+    globs: "list[str]"
+    variable_start: tp.NotRequired[str]
+    variable_end: tp.NotRequired[str]
+    block_start: tp.NotRequired[str]
+    block_end: tp.NotRequired[str]
+    comment_start: tp.NotRequired[str]
+    comment_end: tp.NotRequired[str]
+    trim_blocks: tp.NotRequired[bool]
+    lstrip_blocks: tp.NotRequired[bool]
+    undefined: tp.NotRequired[tp.Literal["strict", "chainable", "lenient"]]
+    recursion_limit: tp.NotRequired[int]
+    fuel: tp.NotRequired[int]
 
 
 class InputContext(tp.TypedDict):
@@ -47,10 +77,33 @@ class Tasks(tp.TypedDict):
     post: tp.NotRequired["list[Task]"]
 
 
+class Formatter(tp.TypedDict):
+    globs: "list[str]"
+    command: str
+
+
+class MatchRule(tp.TypedDict):
+    pattern: str
+    out: tp.NotRequired[str]
+
+
+class Mode(tp.TypedDict):
+    globs: "list[str]"
+    mode: str
+
+
 class InputConfig(tp.TypedDict):
     ignore_files: tp.NotRequired["list[str]"]
     matchers: tp.NotRequired["list[str]"]
+    match_rules: tp.NotRequired["list[MatchRule]"]
     exclude: tp.NotRequired["list[str]"]
+    orphans: tp.NotRequired[tp.Literal["delete", "warn", "keep"]]
+    allow_overwrite: tp.NotRequired["list[str]"]
+    validate: tp.NotRequired["list[str]"]
+    regions: tp.NotRequired["list[str]"]
+    verbatim: tp.NotRequired["list[str]"]
+    formatters: tp.NotRequired["list[Formatter]"]
+    modes: tp.NotRequired["list[Mode]"]
     engine: tp.NotRequired[Engine]
     context: tp.NotRequired[InputContext]
     tasks: tp.NotRequired["Tasks"]
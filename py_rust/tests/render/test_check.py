@@ -0,0 +1,126 @@
+import re
+from pathlib import Path
+
+import pytest
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+from ..helpers.utils import get_lockfile_path, remove_template
+
+
+def test_check_passes_when_up_to_date():
+    """Check mode should succeed and write nothing when all outputs match."""
+    with TmpFileManager() as manager:
+        template = manager.tmpfile(content="Hello, {{ var }}!", suffix=".zetch.txt")
+        config = manager.create_cfg({"context": {"static": {"var": {"value": "World"}}}})
+        cli.render(manager.root_dir, config)
+
+        out_file = Path(remove_template(template))
+        out_mtime = out_file.stat().st_mtime
+        lock_mtime = get_lockfile_path(manager.root_dir).stat().st_mtime
+
+        result = cli.render(manager.root_dir, config, extra_args=["--check"])
+        assert result["debug"]["written"] == []
+        assert result["debug"]["identical"] == [template.name]
+        assert "1 template up to date" in result["stdout"]
+
+        assert out_file.stat().st_mtime == out_mtime
+        assert get_lockfile_path(manager.root_dir).stat().st_mtime == lock_mtime
+
+
+@pytest.mark.parametrize(
+    "desc,modifier",
+    [
+        # Context changed since the last render:
+        (
+            "ctx_changed",
+            lambda manager, template, out_file: manager.create_cfg(
+                {"context": {"static": {"var": {"value": "FOO"}}}}
+            ),
+        ),
+        # Output hand-edited, lockfile hash still matches so only a real contents check catches it:
+        (
+            "output_hand_edited",
+            lambda manager, template, out_file: (
+                out_file.write_text("Hello, edited!"),
+                manager.create_cfg({"context": {"static": {"var": {"value": "World"}}}}),
+            )[1],
+        ),
+        # Output deleted:
+        (
+            "output_missing",
+            lambda manager, template, out_file: (
+                out_file.unlink(),
+                manager.create_cfg({"context": {"static": {"var": {"value": "World"}}}}),
+            )[1],
+        ),
+    ],
+)
+def test_check_fails_when_outdated(desc: str, modifier):
+    """Check mode should fail listing the outdated template, without touching the output or lockfile."""
+    with TmpFileManager() as manager:
+        template = manager.tmpfile(content="Hello, {{ var }}!", suffix=".zetch.txt")
+        cli.render(
+            manager.root_dir,
+            manager.create_cfg({"context": {"static": {"var": {"value": "World"}}}}),
+        )
+
+        out_file = Path(remove_template(template))
+        config = modifier(manager, template, out_file)
+        out_contents = out_file.read_text() if out_file.exists() else None
+        lock_contents = get_lockfile_path(manager.root_dir).read_text()
+
+        with pytest.raises(ValueError, match=re.escape("1 template is out of date")) as e:
+            cli.render(manager.root_dir, config, extra_args=["--check"])
+        assert template.name in str(e.value)
+
+        # Nothing should have been modified:
+        assert (out_file.read_text() if out_file.exists() else None) == out_contents
+        assert get_lockfile_path(manager.root_dir).read_text() == lock_contents
+
+
+def test_check_new_template_writes_nothing():
+    """A never rendered template should fail the check, and neither the output nor lockfile should be created."""
+    with TmpFileManager() as manager:
+        template = manager.tmpfile(content="Hello, World!", suffix=".zetch.txt")
+
+        with pytest.raises(ValueError, match=re.escape("1 template is out of date")):
+            cli.render(manager.root_dir, manager.create_cfg({}), extra_args=["--check"])
+
+        assert not Path(remove_template(template)).exists()
+        assert not get_lockfile_path(manager.root_dir).exists()
+
+
+@pytest.mark.parametrize("when", ["pre", "post"])
+def test_check_skips_tasks(when: str):
+    """Tasks could modify files, so shouldn't be run in check mode."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="Hello, World!", suffix=".zetch.txt")
+        config = manager.create_cfg(
+            {"tasks": {when: [{"commands": ["echo foo > task_ran.txt"]}]}}  # type: ignore
+        )
+        cli.render(manager.root_dir, config)
+
+        # Config is in the root dir, so that's where the task is run from:
+        task_out = Path(manager.root_dir).joinpath("task_ran.txt")
+        assert task_out.exists()
+        task_out.unlink()
+
+        cli.render(manager.root_dir, config, extra_args=["--check"])
+        assert not task_out.exists()
+
+
+def test_check_leaves_config_untouched():
+    """An outdated schema directive in the config is only updated outside check mode."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="Hello, World!", suffix=".zetch.txt")
+        config = manager.create_cfg({})
+        config.write_text("#:schema ./outdated.json\n" + config.read_text())
+        contents = config.read_bytes()
+
+        with pytest.raises(ValueError, match=re.escape("1 template is out of date")):
+            cli.render(manager.root_dir, config, extra_args=["--check"])
+        assert config.read_bytes() == contents
+
+        cli.render(manager.root_dir, config)
+        assert config.read_bytes() != contents
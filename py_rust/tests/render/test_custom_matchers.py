@@ -1,4 +1,6 @@
 import re
+import typing as tp
+from pathlib import Path
 
 import pytest
 
@@ -92,3 +94,50 @@ def test_custom_matchers_invalid():
                         suffix=".toml",
                     ),
                 )
+
+
+def test_match_rules():
+    """Templates matched by rules are named by their out, after the matchers, autoescape by the output's extension."""
+    with TmpFileManager() as manager:
+        names = ["config.h.in", "data.json.j2", "_template_app.yaml", "a.zetch.txt", "other.txt"]
+        for name in names:
+            manager.tmpfile("{{ v }}", full_name=name)
+        # Directories too:
+        manager.tmpfile("{{ v }}", full_name="inner.txt", parent=manager.tmpdir(name="dir.in"))
+        config: InputConfig = {
+            "context": {"static": {"v": {"value": '"x"'}}},
+            "match_rules": [
+                {"pattern": "*.in"},
+                {"pattern": "*.j2"},
+                {"pattern": "_template_*", "out": "generated_*"},
+            ],
+            "engine": {"autoescape": {"json": "json"}},
+        }
+        result = cli.render(manager.root_dir, manager.create_cfg(config))
+
+        assert sorted(result["debug"]["matched_templates"]) == sorted(
+            ["config.h.in", "data.json.j2", "_template_app.yaml", "a.zetch.txt", "dir.in/inner.txt"]
+        )
+        root = Path(manager.root_dir)
+        assert root.joinpath("config.h").read_text() == '"x"'
+        assert root.joinpath("data.json").read_text() == '"\\"x\\""'
+        assert root.joinpath("generated_app.yaml").read_text() == '"x"'
+        assert root.joinpath("a.txt").read_text() == '"x"'
+        assert root.joinpath("dir", "inner.txt").read_text() == '"x"'
+
+
+@pytest.mark.parametrize(
+    "rule,match",
+    [
+        ({"pattern": "*"}, "patterns need a single '*'"),
+        ({"pattern": "*.*"}, "patterns need a single '*'"),
+        ({"pattern": "foo.j2"}, "patterns need a single '*'"),
+        ({"pattern": "*.j2", "out": "out"}, "out needs a single '*'"),
+        ({"pattern": "dir/*.j2"}, "can't contain '/'"),
+    ],
+)
+def test_match_rules_invalid(rule: tp.Dict[str, str], match: str):
+    with TmpFileManager() as manager:
+        with pytest.raises(ValueError, match=re.escape(match)):
+            config: InputConfig = {"match_rules": [rule]}  # type: ignore
+            cli.render(manager.root_dir, manager.create_cfg(config))
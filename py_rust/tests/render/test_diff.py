@@ -0,0 +1,67 @@
+import re
+from pathlib import Path
+
+import pytest
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+from ..helpers.utils import remove_template
+
+
+def strip_ansi(s: str) -> str:
+    return re.sub(r"\x1b\[[0-9;]*m", "", s)
+
+
+def test_diff_shows_changes():
+    """Outputs that change should be shown as unified diffs, identical ones skipped."""
+    with TmpFileManager() as manager:
+        changing = manager.tmpfile(
+            content="line1\nHello, {{ var }}!\nline3\n", full_name="changing.zetch.txt"
+        )
+        manager.tmpfile(content="Static", full_name="static.zetch.txt")
+        cli.render(
+            manager.root_dir,
+            manager.create_cfg({"context": {"static": {"var": {"value": "World"}}}}),
+        )
+
+        result = cli.render(
+            manager.root_dir,
+            manager.create_cfg({"context": {"static": {"var": {"value": "Earth"}}}}),
+            extra_args=["--diff"],
+        )
+        stdout = strip_ansi(result["stdout"])
+        assert (
+            "--- a/changing.txt\n+++ b/changing.txt\n@@ -1,3 +1,3 @@\n line1\n-Hello, World!\n+Hello, Earth!\n line3\n"
+            in stdout
+        )
+        assert "static.txt" not in stdout
+        assert Path(remove_template(changing)).read_text() == "line1\nHello, Earth!\nline3\n"
+
+
+def test_diff_new_output():
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="Hello", full_name="new.zetch.txt")
+        result = cli.render(manager.root_dir, manager.create_cfg({}), extra_args=["--diff"])
+        assert (
+            "--- /dev/null\n+++ b/new.txt\n@@ -0,0 +1 @@\n+Hello\n\\ No newline at end of file\n"
+            in strip_ansi(result["stdout"])
+        )
+
+
+def test_diff_with_check_is_dry_run():
+    """Combined with --check, diffs should be printed without anything being written."""
+    with TmpFileManager() as manager:
+        template = manager.tmpfile(content="Hello, {{ var }}!", suffix=".zetch.txt")
+        cli.render(
+            manager.root_dir,
+            manager.create_cfg({"context": {"static": {"var": {"value": "World"}}}}),
+        )
+
+        with pytest.raises(ValueError, match="1 template is out of date") as e:
+            cli.render(
+                manager.root_dir,
+                manager.create_cfg({"context": {"static": {"var": {"value": "Earth"}}}}),
+                extra_args=["--check", "--diff"],
+            )
+        assert "+Hello, Earth!" in strip_ansi(str(e.value))
+        assert Path(remove_template(template)).read_text() == "Hello, World!"
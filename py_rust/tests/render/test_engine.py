@@ -5,7 +5,7 @@ from pathlib import Path
 
 import pytest
 
-from ..helpers import utils
+from ..helpers import cli, utils
 from ..helpers.tmp_file_manager import TmpFileManager
 from ..helpers.types import Engine, InputConfig
 
@@ -128,6 +128,17 @@ DEFAULT_TEMPLATE_SRC = "Hello, {{ var }}!{# this is an ignored comment #}\nmyboo
             },
             DEFAULT_TEMPLATE_SRC,
         ),
+        # Whitespace control without - markers:
+        (
+            "a\n  {% if mybool %}\n  b\n  {% endif %}\nc\n",
+            {"trim_blocks": True, "lstrip_blocks": True},
+            "a\n  b\nc\n",
+        ),
+        (
+            "a\n  {% if mybool %}\n  b\n  {% endif %}\nc\n",
+            {"trim_blocks": True},
+            "a\n    b\n  c\n",
+        ),
     ],
 )
 def test_engine_config(template_src: str, engine_config: Engine, expected: str):
@@ -179,3 +190,151 @@ def test_undefined_banned(
         else:
             with pytest.raises(ValueError, match=re.escape(expected)):
                 utils.check_single(manager, manager.create_cfg(config), template_src, expected)
+
+
+@pytest.mark.parametrize(
+    "undefined,template_src,expected",
+    [
+        ("strict", "[{{ missing }}]", None),
+        ("lenient", "[{{ missing }}]", "[]"),
+        ("lenient", "[{{ missing.attr }}]", None),
+        ("chainable", "[{{ missing.attr }}]", "[]"),
+    ],
+)
+def test_undefined_behavior(
+    undefined: tp.Literal["strict", "chainable", "lenient"],
+    template_src: str,
+    expected: tp.Optional[str],
+):
+    """Undefined variables are treated as configured, erroring when expected is None."""
+    with TmpFileManager() as manager:
+        config = manager.create_cfg({"engine": {"undefined": undefined}})
+        if expected is None:
+            with pytest.raises(ValueError, match="undefined"):
+                utils.check_single(manager, config, template_src, "")
+        else:
+            utils.check_single(manager, config, template_src, expected)
+
+
+@pytest.mark.parametrize(
+    "engine_config,match",
+    [
+        ({"recursion_limit": 10}, "recursion limit exceeded"),
+        ({"fuel": 100}, "engine ran out of fuel"),
+    ],
+)
+def test_engine_limits(engine_config: Engine, match: str):
+    """Renders exceeding the configured limits should fail, but be fine without them."""
+    template_src = (
+        "{% macro count(n) %}{% if n > 0 %}{{ count(n - 1) }}{% endif %}{{ n }}{% endmacro %}"
+        "{{ count(20) }}"
+    )
+    expected = "".join(str(n) for n in range(21))
+    with TmpFileManager() as manager:
+        utils.check_single(manager, manager.create_cfg({}), template_src, expected)
+    with TmpFileManager() as manager:
+        with pytest.raises(ValueError, match=match):
+            utils.check_single(
+                manager, manager.create_cfg({"engine": engine_config}), template_src, expected
+            )
+
+
+def test_engine_overrides():
+    """Templates matching an override's globs use its options, the first match applying, front matter on top."""
+    with TmpFileManager() as manager:
+        workflows = manager.tmpdir(name="workflows")
+        manager.tmpfile(
+            content="run: ${{ matrix.os }} [[ var ]]", full_name="ci.zetch.yml", parent=workflows
+        )
+        manager.tmpfile(
+            content='+++\n[engine]\nvariable_start = "<<"\nvariable_end = ">>"\n+++\n[[ var ]] << var >>',
+            full_name="fm.zetch.yml",
+            parent=workflows,
+        )
+        manager.tmpfile(content="{{ var }}", full_name="other.zetch.yml")
+        config: InputConfig = {
+            "context": {"static": {"var": {"value": "x"}}},
+            "engine": {
+                "overrides": [
+                    {"globs": ["workflows/*"], "variable_start": "[[", "variable_end": "]]"},
+                    {"globs": ["workflows/ci.zetch.yml"], "variable_start": "<<"},
+                ]
+            },
+        }
+        cli.render(manager.root_dir, manager.create_cfg(config))
+
+        assert workflows.joinpath("ci.yml").read_text() == "run: ${{ matrix.os }} x"
+        assert workflows.joinpath("fm.yml").read_text() == "[[ var ]] x"
+        assert Path(manager.root_dir).joinpath("other.yml").read_text() == "x"
+
+
+@pytest.mark.parametrize(
+    "override,match",
+    [
+        ({"globs": ["*"], "custom_extensions": []}, "Config validation failed"),
+        ({"globs": ["*"], "variable_start": "{{", "block_start": "{{"}, "delimiters"),
+    ],
+)
+def test_engine_overrides_invalid(override: tp.Dict[str, tp.Any], match: str):
+    """Overrides can only contain options that can differ between templates, and need valid syntax."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="x", full_name="x.zetch.txt")
+        with pytest.raises(ValueError, match=match):
+            cli.render(
+                manager.root_dir, manager.create_cfg({"engine": {"overrides": [override]}})
+            )
+
+
+def test_autoescape():
+    """Values are escaped for the output's format when opted into, safe skipping it, other outputs untouched."""
+    value = 'a "b"\nc\'s'
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="{{ v }} {{ v | safe }} {{ n }}", full_name="a.zetch.json")
+        manager.tmpfile(content='k: "{{ v }}"', full_name="b.yaml.zetch")
+        manager.tmpfile(content='k = "{{ v }}"', full_name="c.zetch.toml")
+        manager.tmpfile(content="echo {{ v }} {{ n }} {{ '' }}", full_name="d.zetch.sh")
+        manager.tmpfile(content="{{ v }}", full_name="e.zetch.txt")
+        # Includes are escaped by their own name:
+        manager.tmpfile(content="{{ v }}", full_name="part.json")
+        manager.tmpfile(content="{% include 'part.json' %}", full_name="f.zetch.txt")
+        config: InputConfig = {
+            "context": {"static": {"v": {"value": value}, "n": {"value": 1}}},
+            "engine": {
+                "autoescape": {
+                    "json": "json",
+                    "yaml": "yaml-string",
+                    "toml": "toml-string",
+                    "sh": "shell",
+                }
+            },
+        }
+        cli.render(manager.root_dir, manager.create_cfg(config))
+
+        root = Path(manager.root_dir)
+        assert root.joinpath("a.json").read_text() == '"a \\"b\\"\\nc\'s" ' + value + " 1"
+        assert root.joinpath("b.yaml").read_text() == 'k: "a \\"b\\"\\nc\'s"'
+        assert root.joinpath("c.toml").read_text() == 'k = "a \\"b\\"\\nc\'s"'
+        assert root.joinpath("d.sh").read_text() == "echo 'a \"b\"\nc'\"'\"'s' 1 ''"
+        assert root.joinpath("e.txt").read_text() == value
+        assert root.joinpath("f.txt").read_text() == '"a \\"b\\"\\nc\'s"'
+
+
+def test_autoescape_by_output():
+    """Templates output elsewhere by front matter or foreach are escaped for their output."""
+    value = 'a "b"'
+    with TmpFileManager() as manager:
+        manager.tmpfile(content='+++\nout = "x.json"\n+++\n{{ v }}', full_name="x.zetch.txt")
+        manager.tmpfile(
+            content='+++\nforeach = "EXTS"\nout = "y.{{ item }}"\n+++\n{{ v }}',
+            full_name="y.zetch.txt",
+        )
+        config: InputConfig = {
+            "context": {"static": {"v": {"value": value}, "EXTS": {"value": ["json", "txt"]}}},
+            "engine": {"autoescape": {"json": "json"}},
+        }
+        cli.render(manager.root_dir, manager.create_cfg(config))
+
+        root = Path(manager.root_dir)
+        assert root.joinpath("x.json").read_text() == '"a \\"b\\""'
+        assert root.joinpath("y.json").read_text() == '"a \\"b\\""'
+        assert root.joinpath("y.txt").read_text() == value
@@ -0,0 +1,49 @@
+from pathlib import Path
+
+import pytest
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+
+
+def _create_templates(manager: TmpFileManager):
+    manager.tmpfile(content="ok", full_name="ok.zetch.txt")
+    manager.tmpfile(content="a\n{{ missing_a }}", full_name="a.zetch.txt")
+    manager.tmpfile(content="{{ missing_b + 1 }}", full_name="b.zetch.txt")
+    manager.tmpfile(content="{% if %}", full_name="c.zetch.txt")
+
+
+@pytest.mark.parametrize("jobs", ["1", "4"])
+def test_all_failures_reported(jobs: str):
+    """Every template is rendered and all failures reported together in path order, nothing written."""
+    with TmpFileManager() as manager:
+        _create_templates(manager)
+        with pytest.raises(ValueError) as exc_info:
+            cli.render(manager.root_dir, manager.create_cfg({}), extra_args=["--jobs", jobs])
+
+        msg = str(exc_info.value)
+        assert "3 templates failed to render, nothing has been written." in msg
+        positions = [msg.index(f"(in {name}.zetch.txt:") for name in ["a", "b", "c"]]
+        assert positions == sorted(positions)
+        # With the source excerpt of each, syntax errors included:
+        assert msg.count("Failed to render template.") == 3
+        assert "2| {{ missing_a }} <-- ERR" in msg
+        assert "1| {% if %} <-- ERR" in msg
+        assert "InternalError" not in msg
+        assert not Path(manager.root_dir).joinpath("ok.txt").exists()
+
+
+def test_fail_fast():
+    """--fail-fast stops at the first failure, only reporting that."""
+    with TmpFileManager() as manager:
+        _create_templates(manager)
+        with pytest.raises(ValueError) as exc_info:
+            cli.render(
+                manager.root_dir,
+                manager.create_cfg({}),
+                extra_args=["--jobs", "1", "--fail-fast"],
+            )
+
+        msg = str(exc_info.value)
+        assert "templates failed to render" not in msg
+        assert msg.count("Failed to render template.") == 1
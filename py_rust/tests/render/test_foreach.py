@@ -0,0 +1,122 @@
+import json
+import typing as tp
+from pathlib import Path
+
+import pytest
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+from ..helpers.utils import get_lockfile_path
+
+SERVICE_TEMPLATE = """+++
+foreach = "SERVICES"
+out = "deploy/{{ item.name }}.yaml"
++++
+name: {{ item.name }}
+port: {{ item.port }}
+"""
+
+
+def _cfg(manager: TmpFileManager, services: tp.List[tp.Dict[str, tp.Any]]) -> Path:
+    return manager.create_cfg({"context": {"static": {"SERVICES": {"value": services}}}})
+
+
+def test_foreach():
+    """One output per item, each tracked separately and cleaned up when its item's removed."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content=SERVICE_TEMPLATE, full_name="svc.zetch.yaml")
+        root = Path(manager.root_dir)
+        api = root.joinpath("deploy/api.yaml")
+        web = root.joinpath("deploy/web.yaml")
+
+        cfg = _cfg(manager, [{"name": "api", "port": 1}, {"name": "web", "port": 2}])
+        result = cli.render(manager.root_dir, cfg)
+        assert sorted(result["debug"]["written"]) == [str(api), str(web)]
+        assert api.read_text() == "name: api\nport: 1\n"
+        assert web.read_text() == "name: web\nport: 2\n"
+        assert not root.joinpath("svc.yaml").exists()
+
+        with open(get_lockfile_path(manager.root_dir), "r") as file:
+            assert json.load(file)["outputs"] == {
+                "svc.zetch.yaml -> deploy/api.yaml": "deploy/api.yaml",
+                "svc.zetch.yaml -> deploy/web.yaml": "deploy/web.yaml",
+            }
+
+        # Unchanged inputs, nothing to render:
+        result = cli.render(manager.root_dir, cfg)
+        assert result["debug"]["written"] == []
+        assert result["debug"]["skipped"] == ["svc.zetch.yaml", "svc.zetch.yaml"]
+
+        # Item changed and another removed:
+        result = cli.render(manager.root_dir, _cfg(manager, [{"name": "api", "port": 3}]))
+        assert result["debug"]["written"] == [str(api)]
+        assert api.read_text() == "name: api\nport: 3\n"
+        assert "1 orphaned output deleted" in result["stdout"]
+        assert not web.exists()
+
+
+def test_foreach_expressions():
+    """Foreach is an expression, and skip_if is evaluated per item."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(
+            content=(
+                "+++\n"
+                "foreach = \"SERVICES | selectattr('port')\"\n"
+                'out = "{{ item.name | upper }}.txt"\n'
+                "skip_if = \"item.name == 'skipped'\"\n"
+                'context = { greeting = "Hi" }\n'
+                "+++\n"
+                "{{ greeting }} {{ item.name }}"
+            ),
+            full_name="svc.zetch.txt",
+        )
+        services = [
+            {"name": "a", "port": 1},
+            {"name": "no_port", "port": 0},
+            {"name": "skipped", "port": 2},
+        ]
+        cli.render(manager.root_dir, _cfg(manager, services))
+
+        root = Path(manager.root_dir)
+        assert root.joinpath("A.txt").read_text() == "Hi a"
+        assert not root.joinpath("NO_PORT.txt").exists()
+        assert not root.joinpath("SKIPPED.txt").exists()
+
+
+def test_foreach_unselected():
+    """Outputs of a foreach template that isn't selected shouldn't be treated as orphaned."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content=SERVICE_TEMPLATE, full_name="svc.zetch.yaml")
+        manager.tmpfile(content="other", full_name="other.zetch.txt")
+        cfg = _cfg(manager, [{"name": "api", "port": 1}])
+        cli.render(manager.root_dir, cfg)
+
+        api = Path(manager.root_dir).joinpath("deploy/api.yaml")
+        assert api.exists()
+        cli.render(manager.root_dir, cfg, extra_args=["--only", "other.zetch.txt"])
+        assert api.exists()
+        with open(get_lockfile_path(manager.root_dir), "r") as file:
+            assert "svc.zetch.yaml -> deploy/api.yaml" in json.load(file)["files"]
+
+
+@pytest.mark.parametrize(
+    "front,match",
+    [
+        ('foreach = "SERVICES"', "has foreach without out"),
+        ('foreach = "SERVICES[0].port"\nout = "{{ item }}.txt"', "it should be a list"),
+        ('foreach = "SERVICES"\nout = "same.txt"', "render to the same output 'same.txt'"),
+        ('foreach = "SERVICES"\nout = "../{{ item.name }}.txt"', "isn't a file inside the root"),
+    ],
+)
+def test_foreach_invalid(front: str, match: str):
+    """Invalid foreach usage should fail with a clear error, without writing anything."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content=f"+++\n{front}\n+++\nx", full_name="svc.zetch.txt")
+        with pytest.raises(ValueError, match=match):
+            cli.render(
+                manager.root_dir,
+                _cfg(manager, [{"name": "a", "port": 1}, {"name": "b", "port": 2}]),
+            )
+        assert sorted(p.name for p in Path(manager.root_dir).iterdir() if p.suffix == ".txt") == [
+            "svc.zetch.txt"
+        ]
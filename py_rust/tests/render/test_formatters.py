@@ -0,0 +1,68 @@
+import json
+import typing as tp
+from pathlib import Path
+
+import pytest
+import zetch
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+from ..helpers.types import Formatter
+from ..helpers.utils import get_lockfile_path
+
+
+@pytest.mark.parametrize(
+    "formatters,expected",
+    [
+        ([{"globs": ["*.txt"], "command": "tr a-z A-Z"}], "HELLO, WORLD!"),
+        # Only run on matching outputs:
+        ([{"globs": ["*.json"], "command": "tr a-z A-Z"}], "Hello, World!"),
+        # Run in order, each given the previous output:
+        (
+            [
+                {"globs": ["*.txt"], "command": "sed s/Hello/Bye/"},
+                {"globs": ["**/out.*"], "command": "sed s/Bye/Later/"},
+            ],
+            "Later, World!",
+        ),
+        # Given the output's path:
+        (
+            [{"globs": ["*.txt"], "command": 'basename "$ZETCH_OUTPUT_PATH"'}],
+            "out.txt\n",
+        ),
+    ],
+)
+def test_formatters(formatters: tp.List[Formatter], expected: str):
+    """Outputs should be formatted before being hashed and written, so re-renders are stable."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="Hello, {{ var }}!", full_name="out.zetch.txt")
+        cfg = manager.create_cfg(
+            {"context": {"static": {"var": {"value": "World"}}}, "formatters": formatters}
+        )
+        out_file = Path(manager.root_dir).joinpath("out.txt")
+
+        result = cli.render(manager.root_dir, cfg)
+        assert result["debug"]["written"] == [str(out_file)]
+        assert out_file.read_text() == expected
+        assert cli.run(
+            ["zetch", "render", "--check", manager.root_dir, "--config", str(cfg)]
+        ).endswith("elapsed.")
+
+        # The formatted output should be what's recorded, so the output isn't seen as modified:
+        with open(get_lockfile_path(manager.root_dir), "r") as file:
+            assert json.load(file)["files"]["out.zetch.txt"] == zetch._hash_contents(expected)
+
+
+def test_formatter_failure():
+    """A failing formatter should fail the render, without writing anything."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="Hello", full_name="out.zetch.txt")
+        manager.tmpfile(content="Other", full_name="other.zetch.md")
+        cfg = manager.create_cfg(
+            {"formatters": [{"globs": ["*.txt"], "command": "echo 'bad input' >&2; exit 3"}]}
+        )
+        with pytest.raises(ValueError, match="bad input") as exc:
+            cli.render(manager.root_dir, cfg)
+        assert "Failed to format the output of 'out.zetch.txt'." in str(exc.value)
+        assert not Path(manager.root_dir).joinpath("out.txt").exists()
+        assert not Path(manager.root_dir).joinpath("other.md").exists()
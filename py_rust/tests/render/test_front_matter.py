@@ -0,0 +1,149 @@
+import os
+import typing as tp
+from pathlib import Path
+
+import pytest
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+from ..helpers.types import InputConfig
+
+
+def _cfg(manager: TmpFileManager, extra: tp.Optional[InputConfig] = None, **ctx: tp.Any) -> Path:
+    config: InputConfig = {"context": {"static": {k: {"value": v} for k, v in ctx.items()}}}
+    if extra is not None:
+        config.update(extra)
+    return manager.create_cfg(config)
+
+
+def test_front_matter_stripped():
+    """Front matter shouldn't be rendered, templates without it are unaffected."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(
+            content='+++\ncontext = { a = "x" }\n+++\n{{ a }}\n', full_name="a.zetch.txt"
+        )
+        # Yaml documents start with ---, which isn't front matter:
+        manager.tmpfile(content="---\nb: {{ b }}\n", full_name="b.zetch.yml")
+        # Only front matter when on the first line:
+        manager.tmpfile(content="c\n+++\n{{ b }}\n+++\n", full_name="c.zetch.txt")
+        cli.render(manager.root_dir, _cfg(manager, b="1"))
+
+        root = Path(manager.root_dir)
+        assert root.joinpath("a.txt").read_text() == "x\n"
+        assert root.joinpath("b.yml").read_text() == "---\nb: 1\n"
+        assert root.joinpath("c.txt").read_text() == "c\n+++\n1\n+++\n"
+
+
+def test_front_matter_out():
+    """The output path can be overridden, relative to the template's directory."""
+    with TmpFileManager() as manager:
+        sub = manager.tmpdir(name="sub")
+        manager.tmpfile(
+            content='+++\nout = "../built/renamed.cfg"\n+++\nHello',
+            full_name="a.zetch.txt",
+            parent=sub,
+        )
+        cli.render(manager.root_dir, _cfg(manager))
+
+        root = Path(manager.root_dir)
+        assert root.joinpath("built/renamed.cfg").read_text() == "Hello"
+        assert not sub.joinpath("a.txt").exists()
+
+
+def test_front_matter_context():
+    """Front matter context is local to the template and takes precedence over the global context."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(
+            content='+++\n[context]\nname = "local"\nitems = [1, 2]\n+++\n{{ name }} {{ items | sum }} {{ other }}',
+            full_name="a.zetch.txt",
+        )
+        manager.tmpfile(content="{{ name }}", full_name="b.zetch.txt")
+        cli.render(manager.root_dir, _cfg(manager, name="global", other="o"))
+
+        root = Path(manager.root_dir)
+        assert root.joinpath("a.txt").read_text() == "local 3 o"
+        assert root.joinpath("b.txt").read_text() == "global"
+
+
+def test_front_matter_skip_if():
+    """A template is only rendered when its skip_if is false, its output orphaned when it becomes true."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content='+++\nskip_if = "not enabled"\n+++\nOn', full_name="a.zetch.txt")
+        out_file = Path(manager.root_dir).joinpath("a.txt")
+
+        result = cli.render(manager.root_dir, _cfg(manager, enabled=False))
+        assert result["debug"]["skipped"] == ["a.zetch.txt"]
+        assert not out_file.exists()
+
+        cli.render(manager.root_dir, _cfg(manager, enabled=True))
+        assert out_file.read_text() == "On"
+
+        # The condition's variables are inputs, so the change is picked up:
+        result = cli.render(manager.root_dir, _cfg(manager, {"orphans": "delete"}, enabled=False))
+        assert "1 orphaned output deleted" in result["stdout"]
+        assert not out_file.exists()
+
+
+@pytest.mark.skipif(os.name != "posix", reason="Unix permissions only.")
+def test_front_matter_mode():
+    """The output's permissions are set from the front matter's mode."""
+    with TmpFileManager() as manager:
+        template = manager.tmpfile(
+            content='+++\nmode = "750"\n+++\n#!/bin/sh\necho hi\n', full_name="run.zetch.sh"
+        )
+        cli.render(manager.root_dir, _cfg(manager))
+        out_file = Path(manager.root_dir).joinpath("run.sh")
+        assert out_file.stat().st_mode & 0o7777 == 0o750
+
+        template.write_text('+++\nmode = "644"\n+++\n#!/bin/sh\necho hi\n')
+        cli.render(manager.root_dir, _cfg(manager))
+        assert out_file.stat().st_mode & 0o7777 == 0o644
+
+
+def test_front_matter_engine():
+    """Engine options apply to the template and its includes, others keep the config's."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="[[ var ]]", full_name="part.j2")
+        manager.tmpfile(
+            content='+++\n[engine]\nvariable_start = "[["\nvariable_end = "]]"\n+++\n[[ var ]] {{ keep }} {% include "part.j2" %}',
+            full_name="workflow.zetch.yml",
+        )
+        manager.tmpfile(content="{{ var }}", full_name="other.zetch.txt")
+        cli.render(manager.root_dir, _cfg(manager, var="x"))
+
+        root = Path(manager.root_dir)
+        assert root.joinpath("workflow.yml").read_text() == "x {{ keep }} x"
+        assert root.joinpath("other.txt").read_text() == "x"
+
+
+@pytest.mark.parametrize(
+    "contents,match",
+    [
+        ("+++\nout = \n+++\n", "Invalid front matter in"),
+        ("+++\nunknown = 1\n+++\n", "unknown field"),
+        ('+++\nout = "../../outside.txt"\n+++\n', "isn't a file inside the root"),
+        ('+++\nmode = "999"\n+++\n', "invalid mode"),
+        ("+++\nout = 'a'\n", "is never closed"),
+        ('+++\n[engine]\ncustom_extensions = ["x.py"]\n+++\n', "isn't an engine option"),
+        ('+++\n[engine]\ntemplate_dirs = ["x"]\n+++\n', "isn't an engine option"),
+        ('+++\nskip_if = "1 +"\n+++\n', "Failed to evaluate skip_if"),
+    ],
+)
+def test_front_matter_invalid(contents: str, match: str):
+    """Invalid front matter should fail with a clear error."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content=contents, full_name="a.zetch.txt")
+        with pytest.raises(ValueError, match=match):
+            cli.render(manager.root_dir, _cfg(manager))
+
+
+def test_front_matter_error_lines():
+    """Render errors should point at the line in the file, counting the front matter."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(
+            content='+++\nout = "b.txt"\n+++\nfine\n{{ missing }}\n', full_name="a.zetch.txt"
+        )
+        with pytest.raises(ValueError) as exc:
+            cli.render(manager.root_dir, _cfg(manager))
+        assert "5| {{ missing }} <-- ERR" in str(exc.value)
+        assert "4| fine" in str(exc.value)
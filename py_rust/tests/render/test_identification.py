@@ -168,3 +168,39 @@ def test_ignorefile_overriden_in_exclude():
         )
 
         assert result["debug"]["written"] == [remove_template(template)]
+
+
+def test_output_conflicts():
+    """Outputs from multiple templates, or landing on a template or excluded file, all fail together."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="a", full_name="a.zetch.txt")
+        manager.tmpfile(content="a", full_name="a.txt.zetch")
+        manager.tmpfile(content='+++\nout = "c.zetch.txt"\n+++\nb', full_name="b.zetch.txt")
+        manager.tmpfile(content="c", full_name="c.zetch.txt")
+        manager.tmpfile(content="d", full_name="d.zetch.txt")
+        manager.tmpfile(content='+++\nout = "gen/e.txt"\n+++\ne', full_name="e.zetch.txt")
+        config = manager.create_cfg({"exclude": ["d.txt", "gen/"]})
+
+        with pytest.raises(ValueError) as exc_info:
+            cli.render(manager.root_dir, config)
+
+        msg = str(exc_info.value)
+        assert "Found 4 conflicting template outputs" in msg
+        assert "both render to 'a.txt'." in msg
+        assert "'b.zetch.txt' renders to 'c.zetch.txt', which is itself a template." in msg
+        assert "'d.zetch.txt' renders to 'd.txt', which is excluded." in msg
+        assert "'e.zetch.txt' renders to 'gen/e.txt', which is excluded." in msg
+        assert not Path(manager.root_dir).joinpath("c.txt").exists()
+
+
+def test_output_conflicts_foreach():
+    """Outputs only known once foreach is expanded are checked too."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="x", full_name="x.zetch.txt")
+        manager.tmpfile(
+            content="+++\nforeach = \"['x', 'y']\"\nout = \"{{ item }}.txt\"\n+++\n{{ item }}",
+            full_name="items.zetch.txt",
+        )
+        with pytest.raises(ValueError) as exc_info:
+            cli.render(manager.root_dir, manager.create_cfg({}))
+        assert "'items.zetch.txt' and 'x.zetch.txt' both render to 'x.txt'." in str(exc_info.value)
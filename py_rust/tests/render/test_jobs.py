@@ -0,0 +1,51 @@
+from pathlib import Path
+
+import pytest
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+from ..helpers.utils import get_lockfile_path, remove_template
+
+
+@pytest.mark.parametrize("jobs", ["1", "2", "8"])
+def test_jobs_deterministic(jobs: str):
+    """Parallel rendering should give the same outputs, ordering and lockfile as rendering one by one."""
+    with TmpFileManager() as manager:
+        ext = manager.tmpfile(
+            """import zetch
+@zetch.register_function
+def shout(s):
+    return s.upper() + zetch.context()["var"]
+""",
+            suffix=".py",
+        )
+        templates = [
+            manager.tmpfile(
+                content="{{ shout('t" + str(i) + "') }} {{ var }}",
+                full_name=f"t{i}.zetch.txt",
+            )
+            for i in range(20)
+        ]
+        config = manager.create_cfg(
+            {
+                "context": {"static": {"var": {"value": "!"}}},
+                "engine": {"custom_extensions": [str(ext)]},
+            }
+        )
+
+        sequential = cli.render(manager.root_dir, config, extra_args=["--jobs", "1"])
+        sequential_lock = get_lockfile_path(manager.root_dir).read_text()
+
+        parallel = cli.render(manager.root_dir, config, force=True, extra_args=["--jobs", jobs])
+        assert parallel["debug"]["written"] == sequential["debug"]["written"]
+        assert get_lockfile_path(manager.root_dir).read_text() == sequential_lock
+
+        for i, template in enumerate(templates):
+            assert Path(remove_template(template)).read_text() == f"T{i}! !"
+
+
+def test_jobs_rejects_zero():
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="Hello", suffix=".zetch.txt")
+        with pytest.raises(ValueError, match="--jobs"):
+            cli.render(manager.root_dir, manager.create_cfg({}), extra_args=["--jobs", "0"])
@@ -1,5 +1,7 @@
 import collections
 import json
+import sys
+import typing as tp
 import uuid
 from pathlib import Path
 
@@ -10,6 +12,18 @@ from ..helpers import cli
 from ..helpers.tmp_file_manager import TmpFileManager
 from ..helpers.utils import get_lockfile_path, remove_template
 
+# Formats a file given as its only argument, only writing when changed like real formatters:
+FORMATTER = """
+import sys
+
+with open(sys.argv[1]) as file:
+    contents = file.read()
+formatted = contents.replace(", ", ",\\n")
+if formatted != contents:
+    with open(sys.argv[1], "w") as file:
+        file.write(formatted)
+"""
+
 
 @pytest.mark.parametrize(
     "var1,var2,should_write,force",
@@ -28,33 +42,44 @@ def test_lockfile_caching(var1: str, var2: str, should_write: bool, force: bool)
         contents = "Hello, {{ var }}!"
 
         template = manager.tmpfile(content=contents, suffix=".zetch.txt")
-        result = cli.render(
-            manager.root_dir,
-            manager.create_cfg({"context": {"static": {"var": {"value": var1}}}}),
+        out_name = Path(remove_template(template)).name
+
+        # Simulate some formatting outside of zetch in a post task, shouldn't affect the results:
+        manager.tmpfile(
+            content=FORMATTER,
+            full_name="formatter.py",
         )
+
+        def render(var: str, force: bool = False):
+            return cli.render(
+                manager.root_dir,
+                manager.create_cfg(
+                    {
+                        "context": {"static": {"var": {"value": var}}},
+                        "tasks": {  # type: ignore
+                            "post": [{"commands": [f"{sys.executable} formatter.py {out_name}"]}]
+                        },
+                    },
+                ),
+                force=force,
+            )
+
+        result = render(var1)
         assert result["debug"]["written"] == [remove_template(template)]
         out_file = Path(result["debug"]["written"][0])
-
-        # Simulate some formatting outside of zetch, shouldn't affect the results:
-        with open(out_file, "w") as file:
-            file.write(f"Hello, \n\n{var1}!")
+        assert out_file.read_text() == f"Hello,\n{var1}!"
 
         last_update = Path(result["debug"]["written"][0]).stat().st_mtime
 
         # Second run:
-        result = cli.render(
-            manager.root_dir,
-            manager.create_cfg(
-                {"context": {"static": {"var": {"value": var2}}}},
-            ),
-            force=force,
-        )
+        result = render(var2, force=force)
         if should_write:
             assert result["debug"]["written"] == [remove_template(template)]
             assert out_file.stat().st_mtime > last_update
         else:
             assert result["debug"]["written"] == []
             assert out_file.stat().st_mtime == last_update
+        assert out_file.read_text() == f"Hello,\n{var2}!"
 
 
 @pytest.mark.parametrize(
@@ -85,14 +110,12 @@ def test_corrupt_lockfile(lock_contents: str):
 
         # Should have managed to recreate the lockfile:
         with open(lockfile_path, "r") as file:
-            assert json.load(file) == {
-                "version": zetch.__version__,
-                "files": {
-                    str(template.relative_to(manager.root_dir)): zetch._hash_contents(
-                        "Hello, World!"
-                    ),
-                },
+            lock = json.load(file)
+            assert lock["version"] == zetch.__version__
+            assert lock["files"] == {
+                str(template.relative_to(manager.root_dir)): zetch._hash_contents("Hello, World!"),
             }
+            assert list(lock["deps"]) == [str(template.relative_to(manager.root_dir))]
 
         # If the template is deleted and zetch is run again, it should be removed from the lockfile:
         template.unlink()
@@ -106,6 +129,8 @@ def test_corrupt_lockfile(lock_contents: str):
             assert json.load(file) == {
                 "version": zetch.__version__,
                 "files": {},
+                "deps": {},
+                "outputs": {},
             }
 
 
@@ -148,15 +173,15 @@ def test_lockfile_only_write_when_needed():
         )
         assert result["debug"]["written"] == [remove_template(template1)]
         with open(get_lockfile_path(manager.root_dir), "r") as file:
-            assert json.load(file) == {
-                "version": zetch.__version__,
-                "files": {
-                    # Should be relative to the root_dir as that's where the lockfile is stored:
-                    str(template1.relative_to(manager.root_dir)): zetch._hash_contents(
-                        "Updated, World!"
-                    ),
-                },
+            lock = json.load(file)
+            assert lock["version"] == zetch.__version__
+            assert lock["files"] == {
+                # Should be relative to the root_dir as that's where the lockfile is stored:
+                str(template1.relative_to(manager.root_dir)): zetch._hash_contents(
+                    "Updated, World!"
+                ),
             }
+            assert list(lock["deps"]) == [str(template1.relative_to(manager.root_dir))]
 
 
 def test_lockfile_deterministic_ordering():
@@ -181,3 +206,167 @@ def test_lockfile_deterministic_ordering():
 
             # They should come out in alphabetical order:
             assert [k for k in lock["files"]] == sorted(filenames)
+
+
+@pytest.mark.parametrize(
+    "desc,modifier,rendered",
+    [
+        ("nothing_changed", lambda manager, template, inc, nested: None, ()),
+        (
+            "template_changed",
+            lambda manager, template, inc, nested: template.write_text(
+                "Bye {{ var }} {% include 'inc.txt' %}"
+            ),
+            ("template",),
+        ),
+        (
+            "include_changed",
+            lambda manager, template, inc, nested: inc.write_text(
+                "inc2 {% include 'nested.txt' %}"
+            ),
+            ("template",),
+        ),
+        (
+            "nested_include_changed",
+            lambda manager, template, inc, nested: nested.write_text("nested2 {{ inc_var }}"),
+            ("template",),
+        ),
+        (
+            "read_var_changed",
+            lambda manager, template, inc, nested: manager.create_cfg(
+                {"context": {"static": {"var": {"value": "B"}, "inc_var": {"value": "A"}}}}
+            ),
+            ("template",),
+        ),
+        (
+            "var_read_in_include_changed",
+            lambda manager, template, inc, nested: manager.create_cfg(
+                {"context": {"static": {"var": {"value": "A"}, "inc_var": {"value": "B"}}}}
+            ),
+            ("template",),
+        ),
+        (
+            "unread_var_changed",
+            lambda manager, template, inc, nested: manager.create_cfg(
+                {
+                    "context": {
+                        "static": {
+                            "var": {"value": "A"},
+                            "inc_var": {"value": "A"},
+                            "unread": {"value": "B"},
+                        }
+                    }
+                }
+            ),
+            (),
+        ),
+        (
+            "engine_changed",
+            lambda manager, template, inc, nested: manager.create_cfg(
+                {
+                    "context": {"static": {"var": {"value": "A"}, "inc_var": {"value": "A"}}},
+                    "engine": {"comment_start": "{##", "comment_end": "##}"},
+                }
+            ),
+            ("template", "other"),
+        ),
+    ],
+)
+def test_lockfile_skips_unchanged_inputs(desc: str, modifier, rendered: tp.Tuple[str, ...]):
+    """Templates should only be re-rendered when their source, includes, read context or the engine changes."""
+    with TmpFileManager() as manager:
+        nested = manager.tmpfile(content="nested {{ inc_var }}", full_name="nested.txt")
+        inc = manager.tmpfile(content="inc {% include 'nested.txt' %}", full_name="inc.txt")
+        template = manager.tmpfile(
+            content="Hi {{ var }} {% include 'inc.txt' %}", suffix=".zetch.txt"
+        )
+        # Doesn't read anything, so only re-rendered when the engine changes:
+        other = manager.tmpfile(content="Static", suffix=".zetch.txt")
+
+        config = manager.create_cfg(
+            {
+                "context": {
+                    "static": {
+                        "var": {"value": "A"},
+                        "inc_var": {"value": "A"},
+                        "unread": {"value": "A"},
+                    }
+                }
+            }
+        )
+        cli.render(manager.root_dir, config)
+
+        new_config = modifier(manager, template, inc, nested)
+        if isinstance(new_config, Path):
+            config = new_config
+        result = cli.render(manager.root_dir, config)
+
+        templates = {"template": template.name, "other": other.name}
+        assert sorted(result["debug"]["skipped"]) == sorted(
+            name for key, name in templates.items() if key not in rendered
+        )
+        assert sorted(result["debug"]["matched_templates"]) == sorted(templates.values())
+
+
+def test_lockfile_skip_rerenders_missing_output():
+    """A skipped template would leave a deleted output missing, so it should be rendered again."""
+    with TmpFileManager() as manager:
+        template = manager.tmpfile(content="Hello, {{ var }}!", suffix=".zetch.txt")
+        config = manager.create_cfg({"context": {"static": {"var": {"value": "World"}}}})
+        cli.render(manager.root_dir, config)
+
+        out_file = Path(remove_template(template))
+        out_file.unlink()
+
+        result = cli.render(manager.root_dir, config)
+        assert result["debug"]["skipped"] == []
+        assert result["debug"]["identical"] == [template.name]
+
+
+def test_lockfile_skip_cache_disabled():
+    """Templates opting out of caching in their front matter should be rendered every time."""
+    with TmpFileManager() as manager:
+        cached = manager.tmpfile(content="Hello, {{ var }}!", suffix=".zetch.txt")
+        uncached = manager.tmpfile(
+            content="+++\ncache = false\n+++\nHello, {{ var }}!", suffix=".zetch.txt"
+        )
+        config = manager.create_cfg({"context": {"static": {"var": {"value": "World"}}}})
+        cli.render(manager.root_dir, config)
+
+        result = cli.render(manager.root_dir, config)
+        assert result["debug"]["skipped"] == [cached.name]
+        assert result["debug"]["identical"] == [uncached.name]
+
+
+def test_lockfile_records_deps():
+    """The inputs each template was rendered with should be stored in the lockfile."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="{{ inc_var }}", full_name="inc.txt")
+        template = manager.tmpfile(
+            content="{% for x in [1] %}{{ var }}{{ x }}{% endfor %}{% include 'inc.txt' %}",
+            suffix=".zetch.txt",
+        )
+        cli.render(
+            manager.root_dir,
+            manager.create_cfg(
+                {
+                    "context": {
+                        "static": {
+                            "var": {"value": "A"},
+                            "inc_var": {"value": "B"},
+                            "unread": {"value": "C"},
+                        }
+                    }
+                }
+            ),
+        )
+
+        with open(get_lockfile_path(manager.root_dir), "r") as file:
+            deps = json.load(file)["deps"][template.name]
+        assert deps["source"] == zetch._hash_contents(template.read_text())
+        assert deps["includes"] == {"inc.txt": zetch._hash_contents("{{ inc_var }}")}
+        # Only context actually read should be included, not locals or unread vars:
+        assert deps["ctx"] == {
+            "var": zetch._hash_contents('"A"'),
+            "inc_var": zetch._hash_contents('"B"'),
+        }
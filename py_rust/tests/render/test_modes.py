@@ -0,0 +1,99 @@
+import json
+import os
+from pathlib import Path
+
+import pytest
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+from ..helpers.types import InputConfig
+from ..helpers.utils import get_lockfile_path
+
+pytestmark = pytest.mark.skipif(os.name != "posix", reason="Unix permissions only.")
+
+
+def _mode(path: Path) -> int:
+    return path.stat().st_mode & 0o7777
+
+
+def test_modes_from_template():
+    """Outputs should get the template's permissions, changes to which update the output."""
+    with TmpFileManager() as manager:
+        template = manager.tmpfile(content="#!/bin/sh\necho hi\n", full_name="run.zetch.sh")
+        template.chmod(0o755)
+        out_file = Path(manager.root_dir).joinpath("run.sh")
+
+        cli.render(manager.root_dir, manager.create_cfg({}))
+        assert _mode(out_file) == 0o755
+        with open(get_lockfile_path(manager.root_dir), "r") as file:
+            assert json.load(file)["modes"] == {"run.zetch.sh": "755"}
+
+        # Unchanged, nothing to do:
+        result = cli.render(manager.root_dir, manager.create_cfg({}))
+        assert result["debug"]["written"] == []
+
+        # Only the permissions changed, still counts:
+        template.chmod(0o644)
+        result = cli.render(manager.root_dir, manager.create_cfg({}))
+        assert result["debug"]["written"] == [str(out_file)]
+        assert _mode(out_file) == 0o644
+
+
+def test_modes_changed_by_hand():
+    """Permissions changed by hand should be put back and reported as a write, not silently reset."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="#!/bin/sh\n", full_name="run.zetch.sh").chmod(0o755)
+        out_file = Path(manager.root_dir).joinpath("run.sh")
+        cli.render(manager.root_dir, manager.create_cfg({}))
+
+        out_file.chmod(0o700)
+        result = cli.render(manager.root_dir, manager.create_cfg({}))
+        assert result["debug"]["written"] == [str(out_file)]
+        assert _mode(out_file) == 0o755
+
+        result = cli.render(manager.root_dir, manager.create_cfg({}))
+        assert result["debug"]["written"] == []
+
+
+def test_modes_config():
+    """Config modes override the template's, the front matter overrides both."""
+    with TmpFileManager() as manager:
+        scripts = manager.tmpdir(name="scripts")
+        manager.tmpfile(content="a", full_name="a.zetch.sh", parent=scripts)
+        manager.tmpfile(content='+++\nmode = "700"\n+++\nb', full_name="b.zetch.sh", parent=scripts)
+        manager.tmpfile(content="c", full_name="c.zetch.txt", parent=scripts).chmod(0o600)
+        config: InputConfig = {
+            "modes": [
+                {"globs": ["scripts/*.sh"], "mode": "755"},
+                # The first match wins:
+                {"globs": ["scripts/a.sh"], "mode": "700"},
+            ]
+        }
+        cli.render(manager.root_dir, manager.create_cfg(config))
+
+        assert _mode(scripts.joinpath("a.sh")) == 0o755
+        assert _mode(scripts.joinpath("b.sh")) == 0o700
+        assert _mode(scripts.joinpath("c.txt")) == 0o600
+
+
+def test_modes_check():
+    """Check mode should fail when the output's permissions are wrong, even if the contents match."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="#!/bin/sh\n", full_name="run.zetch.sh").chmod(0o755)
+        cli.render(manager.root_dir, manager.create_cfg({}))
+        cli.render(manager.root_dir, manager.create_cfg({}), extra_args=["--check"])
+
+        Path(manager.root_dir).joinpath("run.sh").chmod(0o644)
+        with pytest.raises(ValueError, match="1 template is out of date"):
+            cli.render(manager.root_dir, manager.create_cfg({}), extra_args=["--check"])
+
+
+def test_modes_invalid():
+    """Modes that aren't octal permissions should be rejected."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="x", full_name="x.zetch.txt")
+        with pytest.raises(ValueError, match="Error reading config file"):
+            cli.render(
+                manager.root_dir,
+                manager.create_cfg({"modes": [{"globs": ["*"], "mode": "999"}]}),
+            )
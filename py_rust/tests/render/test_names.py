@@ -0,0 +1,137 @@
+import typing as tp
+from pathlib import Path
+
+import pytest
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+
+
+def _cfg(manager: TmpFileManager, **ctx: tp.Any) -> Path:
+    return manager.create_cfg({"context": {"static": {k: {"value": v} for k, v in ctx.items()}}})
+
+
+def test_templated_names():
+    """Variables in file and directory names should be rendered in the output path."""
+    with TmpFileManager() as manager:
+        root = Path(manager.root_dir)
+        manager.tmpfile(
+            content="name = '{{ APP_NAME }}'",
+            full_name="config.zetch.toml",
+            parent=manager.tmpdir(name="{{ APP_NAME }}"),
+        )
+        manager.tmpfile(
+            content="# {{ MODULE }}",
+            full_name="{{ MODULE }}.zetch.py",
+            parent=manager.tmpdir(name="src"),
+        )
+
+        cli.render(manager.root_dir, _cfg(manager, APP_NAME="myapp", MODULE="utils"))
+        assert root.joinpath("myapp/config.toml").read_text() == "name = 'myapp'"
+        assert root.joinpath("src/utils.py").read_text() == "# utils"
+
+        # The old output is orphaned when the name changes:
+        result = cli.render(manager.root_dir, _cfg(manager, APP_NAME="other", MODULE="utils"))
+        assert root.joinpath("other/config.toml").read_text() == "name = 'other'"
+        assert not root.joinpath("myapp/config.toml").exists()
+        assert "1 orphaned output deleted" in result["stdout"]
+
+
+def test_template_directories():
+    """Everything inside a directory carrying the matcher is a template, output without the matchers."""
+    with TmpFileManager() as manager:
+        root = Path(manager.root_dir)
+        scaffold = manager.tmpdir(name="scaffold.zetch")
+        manager.tmpfile(content="{{ x }}", full_name="a.txt", parent=scaffold)
+        manager.tmpfile(
+            content="b {{ x }}", full_name="b.md", parent=manager.tmpdir(str(scaffold), "sub")
+        )
+        manager.tmpfile(content="c {{ x }}", full_name="c.zetch.txt", parent=scaffold)
+        # Templated directory names too:
+        manager.tmpfile(
+            content="{{ x }}", full_name="README.md", parent=manager.tmpdir(name="{{ x }}.zetch")
+        )
+
+        result = cli.render(manager.root_dir, _cfg(manager, x="1"))
+        assert sorted(result["debug"]["matched_templates"]) == [
+            "scaffold.zetch/a.txt",
+            "scaffold.zetch/c.zetch.txt",
+            "scaffold.zetch/sub/b.md",
+            "{{ x }}.zetch/README.md",
+        ]
+        assert root.joinpath("scaffold/a.txt").read_text() == "1"
+        assert root.joinpath("scaffold/sub/b.md").read_text() == "b 1"
+        assert root.joinpath("scaffold/c.txt").read_text() == "c 1"
+        assert root.joinpath("1/README.md").read_text() == "1"
+
+        # Unchanged, so nothing re-rendered:
+        result = cli.render(manager.root_dir, _cfg(manager, x="1"))
+        assert result["debug"]["written"] == []
+
+
+def test_template_directories_verbatim():
+    """Binary files and those matching the verbatim globs in template directories should be copied as they are."""
+    with TmpFileManager() as manager:
+        root = Path(manager.root_dir)
+        scaffold = manager.tmpdir(name="scaffold.zetch")
+        manager.tmpfile(content="{{ x }}", full_name="a.txt", parent=scaffold)
+        manager.tmpfile(content="{{ x }}", full_name="raw.txt", parent=scaffold)
+        image = b"\x89PNG\r\n\x1a\n\x00\xff{{ x }}"
+        scaffold.joinpath("logo.png").write_bytes(image)
+        # Not utf-8:
+        scaffold.joinpath("latin.txt").write_bytes("caf\xe9 {{ x }}".encode("latin-1"))
+
+        cfg = manager.create_cfg(
+            {"context": {"static": {"x": {"value": "1"}}}, "verbatim": ["scaffold.zetch/raw.*"]}
+        )
+        result = cli.render(manager.root_dir, cfg)
+        assert sorted(result["debug"]["matched_templates"]) == [
+            "scaffold.zetch/a.txt",
+            "scaffold.zetch/latin.txt",
+            "scaffold.zetch/logo.png",
+            "scaffold.zetch/raw.txt",
+        ]
+        assert root.joinpath("scaffold/a.txt").read_text() == "1"
+        assert root.joinpath("scaffold/raw.txt").read_text() == "{{ x }}"
+        assert root.joinpath("scaffold/logo.png").read_bytes() == image
+        assert root.joinpath("scaffold/latin.txt").read_bytes() == "caf\xe9 {{ x }}".encode(
+            "latin-1"
+        )
+
+        # Unchanged, so nothing copied again:
+        result = cli.render(manager.root_dir, cfg)
+        assert result["debug"]["written"] == []
+        assert cli.run(
+            ["zetch", "render", "--check", manager.root_dir, "--config", str(cfg)]
+        ).endswith("elapsed.")
+
+        scaffold.joinpath("logo.png").write_bytes(image + b"\x00")
+        result = cli.render(manager.root_dir, cfg)
+        assert result["debug"]["written"] == [str(root.joinpath("scaffold/logo.png"))]
+        assert root.joinpath("scaffold/logo.png").read_bytes() == image + b"\x00"
+
+        # Changing the verbatim globs re-renders files no longer or newly copied as they are:
+        cfg = manager.create_cfg(
+            {"context": {"static": {"x": {"value": "1"}}}, "verbatim": ["scaffold.zetch/a.*"]}
+        )
+        cli.render(manager.root_dir, cfg)
+        assert root.joinpath("scaffold/a.txt").read_text() == "{{ x }}"
+        assert root.joinpath("scaffold/raw.txt").read_text() == "1"
+
+
+@pytest.mark.parametrize(
+    "value,match",
+    [
+        ("../..", "isn't a file inside the root"),
+        # Would be an absolute path:
+        ("", "isn't a file inside the root"),
+    ],
+)
+def test_templated_names_invalid(value: str, match: str):
+    """Names rendering outside the root should error."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(
+            content="x", full_name="x.zetch.txt", parent=manager.tmpdir(name="{{ DIR }}")
+        )
+        with pytest.raises(ValueError, match=match):
+            cli.render(manager.root_dir, _cfg(manager, DIR=value))
@@ -0,0 +1,108 @@
+import typing as tp
+from pathlib import Path
+
+import pytest
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+from ..helpers.types import InputConfig
+from ..helpers.utils import remove_template
+
+
+@pytest.mark.parametrize(
+    "config,modify_output,should_delete,expected_msg",
+    [
+        # Deleted by default:
+        ({}, False, True, "1 orphaned output deleted"),
+        ({"orphans": "delete"}, False, True, "1 orphaned output deleted"),
+        # Hand edited since last render, so shouldn't be lost:
+        ({"orphans": "delete"}, True, False, "has been modified since last rendered"),
+        ({"orphans": "warn"}, False, False, "is orphaned"),
+        ({"orphans": "keep"}, False, False, None),
+    ],
+)
+def test_orphans_on_template_removed(
+    config: InputConfig,
+    modify_output: bool,
+    should_delete: bool,
+    expected_msg: tp.Optional[str],
+):
+    """Outputs of removed templates should be handled according to the orphans config."""
+    with TmpFileManager() as manager:
+        template = manager.tmpfile(content="Hello, World!", suffix=".zetch.txt")
+        cfg = manager.create_cfg(config)
+        cli.render(manager.root_dir, cfg)
+
+        out_file = Path(remove_template(template))
+        assert out_file.exists()
+        if modify_output:
+            out_file.write_text("Hand edited!")
+
+        template.unlink()
+        result = cli.render(manager.root_dir, cfg)
+
+        assert out_file.exists() != should_delete
+        if expected_msg is not None:
+            assert expected_msg in result["stdout"]
+        else:
+            assert "orphan" not in result["stdout"]
+
+        # Only warned about orphans stay tracked, so the warning repeats until it's dealt with:
+        result = cli.render(manager.root_dir, cfg)
+        if config.get("orphans") == "warn":
+            assert expected_msg in result["stdout"]
+            out_file.unlink()
+            result = cli.render(manager.root_dir, cfg)
+        assert "orphan" not in result["stdout"]
+
+
+def test_orphans_on_template_renamed():
+    """Renaming a template should delete the old output, unless the new template renders to the same place."""
+    with TmpFileManager() as manager:
+        renamed = manager.tmpfile(content="Renamed", full_name="renamed.zetch.txt")
+        same_out = manager.tmpfile(content="Same", full_name="same.zetch.txt")
+        cfg = manager.create_cfg({})
+        cli.render(manager.root_dir, cfg)
+
+        renamed.rename(Path(manager.root_dir).joinpath("new_name.zetch.txt"))
+        same_out.rename(Path(manager.root_dir).joinpath("same.txt.zetch"))
+        result = cli.render(manager.root_dir, cfg)
+
+        assert not Path(manager.root_dir).joinpath("renamed.txt").exists()
+        assert Path(manager.root_dir).joinpath("new_name.txt").read_text() == "Renamed"
+        assert Path(manager.root_dir).joinpath("same.txt").read_text() == "Same"
+        assert "1 orphaned output deleted: renamed.txt." in result["stdout"]
+
+
+def test_orphans_warned_until_resolved():
+    """Orphans left with warn stay tracked, so switching to delete still cleans them up."""
+    with TmpFileManager() as manager:
+        renamed = manager.tmpfile(content="Renamed", full_name="renamed.zetch.txt")
+        cli.render(manager.root_dir, manager.create_cfg({"orphans": "warn"}))
+
+        renamed.rename(Path(manager.root_dir).joinpath("new_name.zetch.txt"))
+        for _ in range(2):
+            result = cli.render(manager.root_dir, manager.create_cfg({"orphans": "warn"}))
+            assert "its output 'renamed.txt' is orphaned" in result["stdout"]
+
+        result = cli.render(manager.root_dir, manager.create_cfg({"orphans": "delete"}))
+        assert "1 orphaned output deleted: renamed.txt." in result["stdout"]
+        assert not Path(manager.root_dir).joinpath("renamed.txt").exists()
+
+
+def test_orphans_empty_dirs_removed():
+    """Directories only holding deleted orphans are removed with them."""
+    with TmpFileManager() as manager:
+        template = manager.tmpfile(
+            content='+++\nout = "out/nested/a.txt"\n+++\na', full_name="a.zetch.txt"
+        )
+        manager.tmpfile(content='+++\nout = "out/b.txt"\n+++\nb', full_name="b.zetch.txt")
+        cfg = manager.create_cfg({})
+        cli.render(manager.root_dir, cfg)
+        root = Path(manager.root_dir)
+        assert root.joinpath("out", "nested", "a.txt").exists()
+
+        template.unlink()
+        cli.render(manager.root_dir, cfg)
+        assert not root.joinpath("out", "nested").exists()
+        assert root.joinpath("out", "b.txt").exists()
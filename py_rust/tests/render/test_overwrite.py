@@ -0,0 +1,79 @@
+from pathlib import Path
+
+import pytest
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+from ..helpers.types import InputConfig
+from ..helpers.utils import remove_template
+
+
+@pytest.mark.parametrize(
+    "config,force,should_overwrite",
+    [
+        ({}, False, False),
+        ({}, True, True),
+        ({"allow_overwrite": ["*.txt"]}, False, True),
+        ({"allow_overwrite": ["other/**"]}, False, False),
+    ],
+)
+def test_overwrite_untracked_file(config: InputConfig, force: bool, should_overwrite: bool):
+    """A new template shouldn't clobber an existing file zetch didn't write, unless forced or allowed."""
+    with TmpFileManager() as manager:
+        existing = manager.tmpfile(content="Precious", full_name="file.txt")
+        template = manager.tmpfile(content="Rendered", full_name="file.zetch.txt")
+        other = manager.tmpfile(content="Other", suffix=".zetch.json")
+
+        if should_overwrite:
+            cli.render(manager.root_dir, manager.create_cfg(config), force=force)
+            assert existing.read_text() == "Rendered"
+        else:
+            with pytest.raises(ValueError, match="Refusing to overwrite 1 output") as e:
+                cli.render(manager.root_dir, manager.create_cfg(config), force=force)
+            assert "file.txt exists but wasn't written by zetch" in str(e.value)
+            assert existing.read_text() == "Precious"
+            # Nothing should be written when any output is refused:
+            assert not Path(remove_template(other)).exists()
+
+        # Identical contents are safe to adopt:
+        existing.write_text("Rendered")
+        template.write_text("Rendered")
+        cli.render(manager.root_dir, manager.create_cfg({}))
+
+
+def test_overwrite_hand_edited_output():
+    """Manual edits to a rendered file shouldn't be lost on the next render."""
+    with TmpFileManager() as manager:
+        template = manager.tmpfile(content="Hello, {{ var }}!", suffix=".zetch.txt")
+        cli.render(
+            manager.root_dir,
+            manager.create_cfg({"context": {"static": {"var": {"value": "World"}}}}),
+        )
+
+        out_file = Path(remove_template(template))
+        out_file.write_text("Hand edited!")
+
+        config = manager.create_cfg({"context": {"static": {"var": {"value": "Earth"}}}})
+        with pytest.raises(ValueError, match="has been modified since last rendered"):
+            cli.render(manager.root_dir, config)
+        assert out_file.read_text() == "Hand edited!"
+
+        cli.render(manager.root_dir, config, force=True)
+        assert out_file.read_text() == "Hello, Earth!"
+
+
+def test_overwrite_renamed_template():
+    """A renamed template rendering to the same output should still be able to overwrite it."""
+    with TmpFileManager() as manager:
+        template = manager.tmpfile(content="Hello, {{ var }}!", full_name="file.zetch.txt")
+        cli.render(
+            manager.root_dir,
+            manager.create_cfg({"context": {"static": {"var": {"value": "World"}}}}),
+        )
+
+        template.rename(Path(manager.root_dir).joinpath("file.txt.zetch"))
+        cli.render(
+            manager.root_dir,
+            manager.create_cfg({"context": {"static": {"var": {"value": "Earth"}}}}),
+        )
+        assert Path(manager.root_dir).joinpath("file.txt").read_text() == "Hello, Earth!"
@@ -0,0 +1,114 @@
+import typing as tp
+from pathlib import Path
+
+import pytest
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+from ..helpers.types import Preload
+
+MACROS = """{% set sep = SEP * 3 %}
+{% macro header(title) %}{{ sep }} {{ title }} {{ sep }}{% endmacro %}
+{% macro wrap(x) %}[{{ x }}]{% endmacro %}"""
+
+
+def _cfg(manager: TmpFileManager, preload: tp.List[Preload], **ctx: tp.Any) -> Path:
+    return manager.create_cfg(
+        {
+            "engine": {"template_dirs": ["./macros"], "preload": preload},
+            "context": {"static": {k: {"value": v} for k, v in ctx.items()}},
+        }
+    )
+
+
+def test_preload():
+    """Macros and top-level set values are globals, under the alias when given."""
+    with TmpFileManager() as manager:
+        macros = manager.tmpdir(name="macros")
+        common = manager.tmpfile(content=MACROS, full_name="common.j2", parent=macros)
+        manager.tmpfile(content="{{ header('a') }} {{ sep }}", full_name="a.zetch.txt")
+        manager.tmpfile(content="{{ c.header('b') }} {{ c.wrap(c.sep) }}", full_name="b.zetch.txt")
+
+        preload: tp.List[Preload] = [{"template": "common.j2"}, {"template": "common.j2", "as": "c"}]
+        cli.render(manager.root_dir, _cfg(manager, preload, SEP="-"))
+        root = Path(manager.root_dir)
+        assert root.joinpath("a.txt").read_text() == "--- a --- ---"
+        assert root.joinpath("b.txt").read_text() == "--- b --- [---]"
+
+        # Changes to the preloaded template are picked up:
+        common.write_text(MACROS.replace("{{ sep }} {{ title }}", "{{ title }}"))
+        cli.render(manager.root_dir, _cfg(manager, preload, SEP="-"))
+        assert root.joinpath("a.txt").read_text() == "a --- ---"
+        assert root.joinpath("b.txt").read_text() == "b --- [---]"
+
+        # As are changes to the context used by the preloaded values:
+        cli.render(manager.root_dir, _cfg(manager, preload, SEP="+"))
+        assert root.joinpath("b.txt").read_text() == "b +++ [+++]"
+
+
+def test_preload_other_syntax():
+    """Preloads use the config's syntax, so still work in templates with overridden delimiters."""
+    with TmpFileManager() as manager:
+        macros = manager.tmpdir(name="macros")
+        manager.tmpfile(content=MACROS, full_name="common.j2", parent=macros)
+        manager.tmpfile(content="[[ header('a') ]] [[ sep ]]", full_name="a.zetch.yml")
+        manager.tmpfile(
+            content='+++\n[engine]\nvariable_start = "<<"\nvariable_end = ">>"\n+++\n<< wrap(1) >>',
+            full_name="b.zetch.txt",
+        )
+        cfg = manager.create_cfg(
+            {
+                "engine": {
+                    "template_dirs": ["./macros"],
+                    "preload": [{"template": "common.j2"}],
+                    "overrides": [
+                        {"globs": ["*.yml"], "variable_start": "[[", "variable_end": "]]"}
+                    ],
+                },
+                "context": {"static": {"SEP": {"value": "-"}}},
+            }
+        )
+        cli.render(manager.root_dir, cfg)
+        root = Path(manager.root_dir)
+        assert root.joinpath("a.yml").read_text() == "--- a --- ---"
+        assert root.joinpath("b.txt").read_text() == "[1]"
+
+
+def test_preload_values():
+    """Maps and lists set in a preload behave as they would anywhere else."""
+    with TmpFileManager() as manager:
+        macros = manager.tmpdir(name="macros")
+        manager.tmpfile(
+            content='{% set opts = {"a": 1, "b": [2, 3]} %}{% set names = ["x", "y"] %}',
+            full_name="values.j2",
+            parent=macros,
+        )
+        manager.tmpfile(
+            content=(
+                "{{ opts.a }} {{ opts['b'][1] }} {{ opts | length }} {{ opts | tojson }} "
+                "{{ names | join(',') }} {{ names[0] }} {% for k in opts %}{{ k }}{% endfor %}"
+            ),
+            full_name="a.zetch.txt",
+        )
+        cli.render(manager.root_dir, _cfg(manager, [{"template": "values.j2"}]))
+        assert (
+            Path(manager.root_dir).joinpath("a.txt").read_text()
+            == '1 3 2 {"a":1,"b":[2,3]} x,y x ab'
+        )
+
+
+@pytest.mark.parametrize(
+    "preload,match",
+    [
+        ([{"template": "common.j2", "as": "SEP"}], "clashes with a context key"),
+        ([{"template": "common.j2"}, {"template": "common.j2"}], "clashes with a context key"),
+        ([{"template": "madeup.j2"}], "Failed to preload 'madeup.j2'"),
+    ],
+)
+def test_preload_invalid(preload: tp.List[Preload], match: str):
+    """Clashing names and missing templates should fail with a clear error."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content=MACROS, full_name="common.j2", parent=manager.tmpdir(name="macros"))
+        manager.tmpfile(content="x", full_name="x.zetch.txt")
+        with pytest.raises(ValueError, match=match):
+            cli.render(manager.root_dir, _cfg(manager, preload, SEP="-"))
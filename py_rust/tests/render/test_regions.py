@@ -0,0 +1,177 @@
+import json
+import typing as tp
+from pathlib import Path
+
+import pytest
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+from ..helpers.types import InputConfig
+from ..helpers.utils import get_lockfile_path
+
+
+def _version_cfg(manager: TmpFileManager, version: str) -> Path:
+    return manager.create_cfg(
+        {"context": {"static": {"version": {"value": version}}}, "regions": ["**"]}
+    )
+
+
+@pytest.mark.parametrize(
+    "name,contents,expected",
+    [
+        # Html style comments, the old body is replaced:
+        (
+            "README.md",
+            "# Title\n<!-- zetch:start Version {{ version }} -->\nold\n<!-- zetch:end -->\nFooter\n",
+            "# Title\n<!-- zetch:start Version {{ version }} -->\nVersion 1.2.3\n<!-- zetch:end -->\nFooter\n",
+        ),
+        # Hash comments, an empty body is filled:
+        (
+            "Makefile",
+            "# zetch:start VERSION := {{ version }}\n# zetch:end\nall:\n\techo hi\n",
+            "# zetch:start VERSION := {{ version }}\nVERSION := 1.2.3\n# zetch:end\nall:\n\techo hi\n",
+        ),
+        # Multiple regions, multiline bodies:
+        (
+            "lib.rs",
+            '// zetch:start pub const VERSION: &str = "{{ version }}";\n// zetch:end\nfn main() {}\n/* zetch:start {% for i in range(2) %}const A{{ i }}: u8 = {{ i }};{{ "\\n" }}{% endfor %} */\n/* zetch:end */\n',
+            '// zetch:start pub const VERSION: &str = "{{ version }}";\npub const VERSION: &str = "1.2.3";\n// zetch:end\nfn main() {}\n/* zetch:start {% for i in range(2) %}const A{{ i }}: u8 = {{ i }};{{ "\\n" }}{% endfor %} */\nconst A0: u8 = 0;\nconst A1: u8 = 1;\n/* zetch:end */\n',
+        ),
+    ],
+)
+def test_regions(name: str, contents: str, expected: str):
+    """Regions should be rendered in place, leaving the rest of the file and the template text in the markers alone."""
+    with TmpFileManager() as manager:
+        src = manager.tmpfile(content=contents, full_name=name)
+        cfg = _version_cfg(manager, "1.2.3")
+
+        result = cli.render(manager.root_dir, cfg)
+        assert result["debug"]["matched_templates"] == [name]
+        assert src.read_text() == expected
+
+        # Zetch's own write shouldn't make the file look changed:
+        result = cli.render(manager.root_dir, cfg)
+        assert result["debug"]["skipped"] == [name]
+        assert cli.run(
+            ["zetch", "render", "--check", manager.root_dir, "--config", str(cfg)]
+        ).endswith("elapsed.")
+
+
+def test_regions_rerender():
+    """Regions should update when their inputs change, with edits outside the regions kept."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="v{{ version }}", full_name="part.txt")
+        src = manager.tmpfile(
+            content='# zetch:start {% include "part.txt" %}\n# zetch:end\n', full_name="notes.txt"
+        )
+        cli.render(manager.root_dir, _version_cfg(manager, "1"))
+        assert src.read_text() == '# zetch:start {% include "part.txt" %}\nv1\n# zetch:end\n'
+
+        # Context change:
+        result = cli.render(manager.root_dir, _version_cfg(manager, "2"))
+        assert result["debug"]["written"] == [str(src)]
+        assert src.read_text() == '# zetch:start {% include "part.txt" %}\nv2\n# zetch:end\n'
+
+        # Include change:
+        Path(manager.root_dir).joinpath("part.txt").write_text("V{{ version }}")
+        cli.render(manager.root_dir, _version_cfg(manager, "2"))
+        assert src.read_text() == '# zetch:start {% include "part.txt" %}\nV2\n# zetch:end\n'
+
+        # Edits outside the regions are kept:
+        src.write_text("Intro\n" + src.read_text())
+        cli.render(manager.root_dir, _version_cfg(manager, "3"))
+        assert src.read_text() == 'Intro\n# zetch:start {% include "part.txt" %}\nV3\n# zetch:end\n'
+
+
+def test_regions_hand_edited():
+    """Hand edits inside a region shouldn't be silently lost on the next render."""
+    with TmpFileManager() as manager:
+        src = manager.tmpfile(
+            content="# zetch:start {{ version }}\n# zetch:end\n", full_name="a.txt"
+        )
+        cli.render(manager.root_dir, _version_cfg(manager, "1"))
+
+        src.write_text("# zetch:start {{ version }}\nmine\n# zetch:end\n")
+        with pytest.raises(
+            ValueError, match="has a templated region that's been modified since last rendered"
+        ):
+            cli.render(manager.root_dir, _version_cfg(manager, "2"))
+        assert src.read_text() == "# zetch:start {{ version }}\nmine\n# zetch:end\n"
+
+        cli.render(manager.root_dir, _version_cfg(manager, "2"), force=True)
+        assert src.read_text() == "# zetch:start {{ version }}\n2\n# zetch:end\n"
+
+
+def test_regions_lockfile():
+    """Region files are tracked by their region hashes, never cleaned up as orphaned outputs."""
+    with TmpFileManager() as manager:
+        src = manager.tmpfile(
+            content="# zetch:start {{ version }}\n# zetch:end\n", full_name="a.txt"
+        )
+        cfg = _version_cfg(manager, "1")
+        cli.render(manager.root_dir, cfg)
+
+        with open(get_lockfile_path(manager.root_dir), "r") as file:
+            contents = json.load(file)
+        assert "a.txt" in contents["files"]
+        assert len(contents["regions"]["a.txt"]) == 1
+        assert "a.txt" not in contents["outputs"]
+
+        # No longer templated, the file should stay and its entry be dropped:
+        src.write_text("Plain now\n")
+        cli.render(manager.root_dir, manager.create_cfg({"orphans": "delete", "regions": ["**"]}))
+        assert src.read_text() == "Plain now\n"
+        with open(get_lockfile_path(manager.root_dir), "r") as file:
+            contents = json.load(file)
+        assert "a.txt" not in contents["files"]
+        assert "regions" not in contents
+
+
+@pytest.mark.parametrize(
+    "contents,match",
+    [
+        ("# zetch:start {{ version }}\n", "region is never closed"),
+        ("# zetch:end\n", "without a matching"),
+        ("# zetch:start a\n# zetch:start b\n# zetch:end\n", "before the region opened on line 1"),
+        ('# zetch:start {{ "# zetch:end" }}\n# zetch:end\n', "rendered region markers"),
+    ],
+)
+def test_regions_invalid(contents: str, match: str):
+    """Unbalanced markers should error rather than render something unexpected."""
+    with TmpFileManager() as manager:
+        src = manager.tmpfile(content=contents, full_name="a.txt")
+        with pytest.raises(ValueError, match=match):
+            cli.render(manager.root_dir, _version_cfg(manager, "1"))
+        assert src.read_text() == contents
+
+
+def test_regions_not_comments():
+    """Markers that aren't on their own comment line, e.g. in strings, shouldn't be treated as regions."""
+    with TmpFileManager() as manager:
+        contents = 'x = "zetch:start {{ version }}"\ny = "zetch:end"\n'
+        src = manager.tmpfile(content=contents, full_name="a.py")
+        result = cli.render(manager.root_dir, _version_cfg(manager, "1"))
+        assert result["debug"]["matched_templates"] == []
+        assert src.read_text() == contents
+
+
+@pytest.mark.parametrize(
+    "regions,expected",
+    [
+        # Opt in, nothing searched by default:
+        (None, []),
+        (["docs/**"], ["docs/a.md"]),
+        (["*.md", "!docs/**"], ["b.md"]),
+    ],
+)
+def test_regions_globs(regions: tp.Optional[tp.List[str]], expected: tp.List[str]):
+    """Only files matching the regions globs in the config should be searched for regions."""
+    with TmpFileManager() as manager:
+        contents = "<!-- zetch:start {{ version }} -->\n<!-- zetch:end -->\n"
+        manager.tmpfile(content=contents, full_name="a.md", parent=manager.tmpdir(name="docs"))
+        manager.tmpfile(content=contents, full_name="b.md")
+        config: InputConfig = {"context": {"static": {"version": {"value": "1"}}}}
+        if regions is not None:
+            config["regions"] = regions
+        result = cli.render(manager.root_dir, manager.create_cfg(config))
+        assert sorted(result["debug"]["matched_templates"]) == expected
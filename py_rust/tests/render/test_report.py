@@ -0,0 +1,106 @@
+import json
+import subprocess
+from pathlib import Path
+
+import pytest
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+
+
+def test_report_contents():
+    """The report should describe each template handled along with the overall render."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="Hello, {{ var }}!", full_name="changing.zetch.txt")
+        manager.tmpfile(content="Static", full_name="static.zetch.txt")
+        report_path = Path(manager.root_dir).joinpath("report.json")
+
+        def render(var: str):
+            config = manager.create_cfg(
+                {
+                    "context": {"static": {"var": {"value": var}}},
+                    "tasks": {"post": [{"commands": ["echo post"]}]},
+                }
+            )
+            cli.render(manager.root_dir, config, extra_args=["--report", str(report_path)])
+            return config, json.loads(report_path.read_text())
+
+        config, report = render("World")
+        assert report["config"] == str(config.resolve())
+        assert report["root"] == str(Path(manager.root_dir).resolve())
+        assert report["lockfile_modified"] is True
+        assert report["tasks_run"] == 1
+        assert report["error"] is None
+        assert sorted(
+            (t["source"], t["output"], t["status"]) for t in report["templates"]
+        ) == [
+            ("changing.zetch.txt", "changing.txt", "written"),
+            ("static.zetch.txt", "static.txt", "written"),
+        ]
+        assert all(t["duration_ms"] >= 0 for t in report["templates"])
+        assert "Rendering templates" in [t["description"] for t in report["timings"]]
+
+        _, report = render("Earth")
+        assert sorted((t["source"], t["status"]) for t in report["templates"]) == [
+            ("changing.zetch.txt", "written"),
+            ("static.zetch.txt", "skipped"),
+        ]
+
+        _, report = render("Earth")
+        assert report["lockfile_modified"] is False
+        assert [t["status"] for t in report["templates"]] == ["skipped", "skipped"]
+
+
+def test_report_on_failure():
+    """The report should still be written when rendering fails, with the failing template and error."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="{{ undefined_fn() }}", full_name="bad.zetch.txt")
+        report_path = Path(manager.root_dir).joinpath("report.json")
+
+        with pytest.raises(ValueError, match="Failed to render template."):
+            cli.render(
+                manager.root_dir,
+                manager.create_cfg({}),
+                extra_args=["--report", str(report_path)],
+            )
+
+        report = json.loads(report_path.read_text())
+        assert [(t["source"], t["status"]) for t in report["templates"]] == [
+            ("bad.zetch.txt", "failed")
+        ]
+        assert report["error"]["kind"] == "RenderTemplateError"
+        assert "Failed to render template." in report["error"]["messages"]
+        assert not Path(manager.root_dir).joinpath("bad.txt").exists()
+
+
+def test_report_stdout():
+    """Reporting to '-' should print only the json, in place of the summary line."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="Hello", full_name="file.zetch.txt")
+        output = cli.run(
+            [
+                "zetch",
+                str(manager.root_dir),
+                "--config",
+                str(manager.create_cfg({})),
+                "--report",
+                "-",
+            ]
+        )
+        report = json.loads(output)
+        assert [(t["output"], t["status"]) for t in report["templates"]] == [
+            ("file.txt", "written")
+        ]
+
+
+def test_report_stdout_with_diff():
+    """Diffs go to stderr when the report's printed to stdout, so it can still be parsed."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="Hello", full_name="file.zetch.txt")
+        args = ["zetch", str(manager.root_dir), "--config", str(manager.create_cfg({}))]
+        result = subprocess.run(
+            [*args, "--report", "-", "--diff"], capture_output=True, text=True, check=True
+        )
+        report = json.loads(result.stdout)
+        assert [t["output"] for t in report["templates"]] == ["file.txt"]
+        assert "+Hello" in result.stderr
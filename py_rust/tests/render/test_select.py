@@ -0,0 +1,136 @@
+import json
+import os
+import subprocess
+import typing as tp
+from pathlib import Path
+
+import pytest
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+from ..helpers.utils import get_lockfile_path
+
+
+def _setup(manager: TmpFileManager) -> tp.Callable[[str, tp.List[str]], cli.RenderResult]:
+    api = manager.tmpdir(name="api", parent=manager.tmpdir(name="services"))
+    web = manager.tmpdir(name="web", parent=Path(manager.root_dir).joinpath("services"))
+    manager.tmpfile(content="api {{ var }}", full_name="api.zetch.txt", parent=api)
+    manager.tmpfile(content="web {{ var }}", full_name="web.zetch.txt", parent=web)
+    manager.tmpfile(content="top {{ var }}", full_name="top.zetch.txt")
+
+    def render(var: str, extra_args: tp.List[str]):
+        return cli.render(
+            manager.root_dir,
+            manager.create_cfg({"context": {"static": {"var": {"value": var}}}}),
+            extra_args=extra_args,
+        )
+
+    render("A", [])
+    return render
+
+
+@pytest.mark.parametrize(
+    "extra_args,expected",
+    [
+        (["--only", "services/api/**"], ["services/api/api.zetch.txt"]),
+        (
+            ["--only", "services/**", "--only", "top*"],
+            ["services/api/api.zetch.txt", "services/web/web.zetch.txt", "top.zetch.txt"],
+        ),
+        (
+            ["--only", "*.zetch.txt"],
+            ["services/api/api.zetch.txt", "services/web/web.zetch.txt", "top.zetch.txt"],
+        ),
+        (["{root}/top.zetch.txt"], ["top.zetch.txt"]),
+        (["{root}/services/web"], ["services/web/web.zetch.txt"]),
+        (
+            ["{root}/top.zetch.txt", "--only", "services/api/*"],
+            ["services/api/api.zetch.txt", "top.zetch.txt"],
+        ),
+        (["--only", "nothing/**"], []),
+    ],
+)
+def test_select_templates(extra_args: tp.List[str], expected: tp.List[str]):
+    """Only the selected templates should be rendered, the rest of the lockfile should be left alone."""
+    with TmpFileManager() as manager:
+        render = _setup(manager)
+        lock_before = json.loads(get_lockfile_path(manager.root_dir).read_text())
+
+        result = render("B", [arg.format(root=manager.root_dir) for arg in extra_args])
+        assert sorted(result["debug"]["matched_templates"]) == expected
+
+        for rel_path in [
+            "services/api/api.zetch.txt",
+            "services/web/web.zetch.txt",
+            "top.zetch.txt",
+        ]:
+            out_path = Path(manager.root_dir).joinpath(rel_path.replace(".zetch", ""))
+            assert out_path.read_text().endswith("B" if rel_path in expected else "A")
+
+        lock_after = json.loads(get_lockfile_path(manager.root_dir).read_text())
+        assert sorted(lock_after["files"]) == sorted(lock_before["files"])
+        for rel_path in lock_before["files"]:
+            if rel_path not in expected:
+                assert lock_after["files"][rel_path] == lock_before["files"][rel_path]
+                assert lock_after["deps"][rel_path] == lock_before["deps"][rel_path]
+
+
+def test_select_leaves_unselected_orphans():
+    """Removed templates outside the selection shouldn't be treated as orphans until they're selected."""
+    with TmpFileManager() as manager:
+        render = _setup(manager)
+        web_template = Path(manager.root_dir).joinpath("services/web/web.zetch.txt")
+        web_out = web_template.with_name("web.txt")
+        web_template.unlink()
+
+        result = render("A", ["--only", "services/api/**"])
+        assert "orphan" not in result["stdout"]
+        assert web_out.exists()
+        assert "services/web/web.zetch.txt" in json.loads(
+            get_lockfile_path(manager.root_dir).read_text()
+        )["files"]
+
+        result = render("A", ["--only", "services/web/**"])
+        assert "1 orphaned output deleted: services/web/web.txt." in result["stdout"]
+        assert not web_out.exists()
+
+
+def test_select_path_outside_root():
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="Hello", full_name="file.zetch.txt")
+        with pytest.raises(ValueError, match="is not inside the root"):
+            cli.render(
+                manager.root_dir,
+                manager.create_cfg({}),
+                extra_args=[os.path.dirname(manager.root_dir)],
+            )
+
+
+@pytest.mark.parametrize(
+    "args",
+    [
+        ["top.zetch.txt"],
+        ["render", "top.zetch.txt"],
+        ["render", "services/web/web.zetch.txt"],
+    ],
+)
+def test_select_single_template(args: tp.List[str]):
+    """A lone template given in place of the root should be rendered with the current directory as the root."""
+    with TmpFileManager() as manager:
+        _setup(manager)
+
+        config = manager.create_cfg({"context": {"static": {"var": {"value": "B"}}}})
+        subprocess.run(
+            ["zetch", *args, "--config", str(config)],
+            cwd=manager.root_dir,
+            check=True,
+        )
+
+        selected = args[-1]
+        for rel_path in [
+            "services/api/api.zetch.txt",
+            "services/web/web.zetch.txt",
+            "top.zetch.txt",
+        ]:
+            out_path = Path(manager.root_dir).joinpath(rel_path.replace(".zetch", ""))
+            assert out_path.read_text().endswith("B" if rel_path == selected else "A")
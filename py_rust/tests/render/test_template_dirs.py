@@ -0,0 +1,101 @@
+import tempfile
+import typing as tp
+from pathlib import Path
+
+import pytest
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+
+
+def _cfg(
+    manager: TmpFileManager,
+    template_dirs: tp.List[str],
+    relative_includes: bool = False,
+    **ctx: tp.Any,
+) -> Path:
+    return manager.create_cfg(
+        {
+            "engine": {"template_dirs": template_dirs, "relative_includes": relative_includes},
+            "context": {"static": {k: {"value": v} for k, v in ctx.items()}},
+        }
+    )
+
+
+def test_template_dirs():
+    """Names are searched for in the root then each template dir in order, the dirs never rendered themselves."""
+    with TmpFileManager() as manager, tempfile.TemporaryDirectory() as outside:
+        macros = manager.tmpdir(name="macros")
+        manager.tmpfile(
+            content="{% macro greet(x) %}Hi {{ x }}{% endmacro %}",
+            full_name="lib.zetch.j2",
+            parent=macros,
+        )
+        manager.tmpfile(content="root", full_name="shared.txt")
+        manager.tmpfile(content="macros", full_name="shared.txt", parent=macros)
+        Path(outside).joinpath("shared.txt").write_text("outside")
+        Path(outside).joinpath("other.txt").write_text("{{ var }}")
+        manager.tmpfile(
+            content=(
+                '{% from "lib.zetch.j2" import greet %}{{ greet("a") }} '
+                '{% include "shared.txt" %} {% include "other.txt" %} '
+                f'{{% include "{outside}/shared.txt" %}}'
+            ),
+            full_name="out.zetch.txt",
+        )
+
+        cfg = _cfg(manager, ["./macros", outside], var="v1")
+        result = cli.render(manager.root_dir, cfg)
+        assert result["debug"]["matched_templates"] == ["out.zetch.txt"]
+        root = Path(manager.root_dir)
+        assert root.joinpath("out.txt").read_text() == "Hi a root v1 outside"
+        assert not macros.joinpath("lib.j2").exists()
+
+        # Includes from the template dirs are tracked like any other:
+        result = cli.render(manager.root_dir, cfg)
+        assert result["debug"]["written"] == []
+        Path(outside).joinpath("other.txt").write_text("changed {{ var }}")
+        result = cli.render(manager.root_dir, cfg)
+        assert root.joinpath("out.txt").read_text() == "Hi a root changed v1 outside"
+
+
+def test_template_dirs_relative_names():
+    """With relative_includes, ./ and ../ names are relative to the including template."""
+    with TmpFileManager() as manager:
+        sub = manager.tmpdir(name="sub")
+        manager.tmpfile(content="sibling", full_name="part.txt", parent=sub)
+        manager.tmpfile(content="root", full_name="top.txt")
+        manager.tmpfile(
+            content='{% include "./part.txt" %} {% include "../top.txt" %} {% include "./top.txt" %}',
+            full_name="a.zetch.txt",
+            parent=sub,
+        )
+        cli.render(manager.root_dir, _cfg(manager, [], relative_includes=True))
+        assert sub.joinpath("a.txt").read_text() == "sibling root root"
+
+
+@pytest.mark.parametrize("relative_includes,expected", [(False, "root"), (True, "sibling")])
+def test_template_dirs_relative_names_ambiguous(relative_includes: bool, expected: str):
+    """./ means the root unless relative_includes is set, even with a matching sibling."""
+    with TmpFileManager() as manager:
+        sub = manager.tmpdir(name="sub")
+        manager.tmpfile(content="sibling", full_name="part.txt", parent=sub)
+        manager.tmpfile(content="root", full_name="part.txt")
+        manager.tmpfile(content='{% include "./part.txt" %}', full_name="a.zetch.txt", parent=sub)
+        cli.render(manager.root_dir, _cfg(manager, [], relative_includes=relative_includes))
+        assert sub.joinpath("a.txt").read_text() == expected
+
+
+@pytest.mark.parametrize(
+    "template_dir,match",
+    [
+        ("./madeup", "does not exist."),
+        ("./file.txt", "is not a directory."),
+    ],
+)
+def test_template_dirs_invalid(template_dir: str, match: str):
+    """Template dirs have to be existing directories."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="x", full_name="file.txt")
+        with pytest.raises(ValueError, match=match):
+            cli.render(manager.root_dir, _cfg(manager, [template_dir]))
@@ -0,0 +1,92 @@
+import os
+from pathlib import Path
+
+import pytest
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+from ..helpers.utils import get_lockfile_path
+
+
+def _files(root: str) -> "set[str]":
+    return {
+        os.path.relpath(os.path.join(parent, name), root)
+        for parent, dirs, files in os.walk(root)
+        for name in files + dirs
+    }
+
+
+def test_failed_sync_leaves_tree_untouched():
+    """When any output can't be written, none are, the lockfile's unchanged and nothing staged is left behind."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="a", full_name="a.zetch.txt")
+        manager.tmpfile(content='+++\nout = "new/nested/b.txt"\n+++\nb', full_name="b.zetch.txt")
+        manager.tmpfile(content="z", full_name="z.zetch.txt")
+        # Fails after the others are staged, its output being a directory (forced past the overwrite checks):
+        manager.tmpdir(name="z.txt")
+        config = manager.create_cfg({})
+        before = _files(manager.root_dir)
+
+        with pytest.raises(ValueError, match="is a directory"):
+            cli.render(manager.root_dir, config, force=True)
+
+        assert _files(manager.root_dir) == before
+        assert not os.path.exists(get_lockfile_path(manager.root_dir))
+
+        # Fine once fixed:
+        os.rmdir(os.path.join(manager.root_dir, "z.txt"))
+        cli.render(manager.root_dir, config)
+        root = Path(manager.root_dir)
+        assert root.joinpath("a.txt").read_text() == "a"
+        assert root.joinpath("new", "nested", "b.txt").read_text() == "b"
+        assert root.joinpath("z.txt").read_text() == "z"
+        assert not any(name.endswith(".zetch-staged") for name in _files(manager.root_dir))
+
+
+@pytest.mark.skipif(os.name != "posix", reason="Unix permissions only.")
+def test_rewritten_regions_keep_permissions():
+    """Outputs are replaced rather than written in place, hand written files with regions keep their permissions."""
+    with TmpFileManager() as manager:
+        src = manager.tmpfile(content="# zetch:start {{ v }}\n# zetch:end\n", full_name="run.sh")
+        src.chmod(0o750)
+        cli.render(
+            manager.root_dir,
+            manager.create_cfg({"context": {"static": {"v": {"value": 1}}}, "regions": ["*.sh"]}),
+        )
+        assert src.read_text() == "# zetch:start {{ v }}\n1\n# zetch:end\n"
+        assert src.stat().st_mode & 0o7777 == 0o750
+
+
+def test_failed_commit_rolls_back():
+    """A failed move puts back the outputs already moved, with nothing staged left behind."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="new", full_name="a.zetch.txt")
+        manager.tmpfile(content="old", full_name="a.txt")
+        manager.tmpfile(content="fresh", full_name="b.zetch.txt")
+        # Only fails once committing, the second creating a directory where the first goes:
+        manager.tmpfile(content='+++\nout = "clash"\n+++\nfile', full_name="d.zetch.txt")
+        manager.tmpfile(content='+++\nout = "clash/inner.txt"\n+++\ninner', full_name="c.zetch.txt")
+        config = manager.create_cfg({})
+        before = _files(manager.root_dir)
+
+        with pytest.raises(ValueError, match="Failed to move staged output into place"):
+            cli.render(manager.root_dir, config, force=True)
+
+        assert _files(manager.root_dir) == before
+        assert Path(manager.root_dir).joinpath("a.txt").read_text() == "old"
+        assert not os.path.exists(get_lockfile_path(manager.root_dir))
+
+
+def test_failed_post_task_keeps_lockfile_in_sync():
+    """Outputs are recorded before post tasks run, so a failing one doesn't leave them untracked."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="a", full_name="a.zetch.txt")
+        failing = manager.create_cfg({"tasks": {"post": [{"commands": ["exit 1"]}]}})
+
+        with pytest.raises(ValueError):
+            cli.render(manager.root_dir, failing)
+        assert Path(manager.root_dir).joinpath("a.txt").read_text() == "a"
+
+        # Not refused as an output zetch didn't write:
+        result = cli.render(manager.root_dir, manager.create_cfg({}))
+        assert result["debug"]["written"] == []
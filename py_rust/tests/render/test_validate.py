@@ -0,0 +1,55 @@
+import typing as tp
+from pathlib import Path
+
+import pytest
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+
+
+@pytest.mark.parametrize(
+    "filename,contents,validate,err",
+    [
+        ("config.zetch.json", '{"a": {{ var }}}', ["*.json"], None),
+        ("config.zetch.json", '{"a": {{ var }}', ["*.json"], "Invalid Json."),
+        ("values.zetch.yaml", "a: {{ var }}\n b: c", ["*.yaml"], "Invalid Yaml."),
+        ("values.zetch.yml", "a: [{{ var }}", ["**/*.yml"], "Invalid Yaml."),
+        ("conf.zetch.toml", "a = {{ var }}\na = 2", ["*.toml"], "Invalid Toml."),
+        # Opt-in, so not validated unless matched:
+        ("config.zetch.json", '{"a": {{ var }}', [], None),
+        ("config.zetch.json", '{"a": {{ var }}', ["*.yaml"], None),
+        # Unknown filetypes are ignored even when matched:
+        ("script.zetch.sh", "{{ var }} {", ["*"], None),
+    ],
+)
+def test_validate_outputs(
+    filename: str, contents: str, validate: tp.List[str], err: tp.Optional[str]
+):
+    """Outputs matching the validate globs should be parsed as their filetype before anything's written."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content="{{ var }}", full_name="valid.zetch.txt")
+        manager.tmpfile(content=contents, full_name=filename)
+        cfg = manager.create_cfg(
+            {"context": {"static": {"var": {"value": 1}}}, "validate": validate}
+        )
+
+        if err is None:
+            cli.render(manager.root_dir, cfg)
+            assert Path(manager.root_dir).joinpath(filename.replace(".zetch", "")).exists()
+        else:
+            with pytest.raises(ValueError, match=err) as exc:
+                cli.render(manager.root_dir, cfg)
+            assert f"Template '{filename}' rendered invalid" in str(exc.value)
+            # Nothing should have been written, including the valid template:
+            assert not Path(manager.root_dir).joinpath(filename.replace(".zetch", "")).exists()
+            assert not Path(manager.root_dir).joinpath("valid.txt").exists()
+
+
+def test_validate_config_changed():
+    """Adding validate globs re-checks outputs rendered before, rather than skipping them as unchanged."""
+    with TmpFileManager() as manager:
+        manager.tmpfile(content='{"a": 1', full_name="config.zetch.json")
+        cli.render(manager.root_dir, manager.create_cfg({}))
+
+        with pytest.raises(ValueError, match="Invalid Json."):
+            cli.render(manager.root_dir, manager.create_cfg({"validate": ["*.json"]}))
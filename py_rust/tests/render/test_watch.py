@@ -0,0 +1,176 @@
+import os
+import signal
+import time
+import typing as tp
+from pathlib import Path
+
+import pytest
+
+from ..helpers import cli
+from ..helpers.tmp_file_manager import TmpFileManager
+from ..helpers.utils import remove_template
+
+pytestmark = pytest.mark.skipif(os.name == "nt", reason="Relies on SIGINT to stop the watcher.")
+
+
+def wait_for(condition: tp.Callable[[], bool], timeout: float = 10):
+    start = time.time()
+    while not condition():
+        if time.time() - start > timeout:
+            raise TimeoutError("Condition not met in time.")
+        time.sleep(0.05)
+
+
+def read_or_none(path: Path) -> tp.Optional[str]:
+    return path.read_text() if path.exists() else None
+
+
+def stop(process: "tp.Any") -> str:
+    """Stop the watcher like a user pressing Ctrl-C, it should exit cleanly."""
+    process.send_signal(signal.SIGINT)
+    stdout, _ = process.communicate(timeout=10)
+    print(stdout)
+    assert process.returncode == 0, stdout
+    return stdout
+
+
+def test_watch_rerenders_on_changes():
+    """Templates, files they include and the config should all trigger re-renders."""
+    with TmpFileManager() as manager:
+        included = manager.tmpfile(content="inc1", full_name="included.txt")
+        template = manager.tmpfile(
+            content="Hello, {{ var }}! {% include 'included.txt' %}", suffix=".zetch.txt"
+        )
+        out_file = Path(remove_template(template))
+        config = manager.create_cfg({"context": {"static": {"var": {"value": "World"}}}})
+
+        process = cli.watch(manager.root_dir, config)
+        try:
+            # Initial render:
+            wait_for(lambda: read_or_none(out_file) == "Hello, World! inc1")
+
+            # Template changed:
+            template.write_text("Goodbye, {{ var }}! {% include 'included.txt' %}")
+            wait_for(lambda: read_or_none(out_file) == "Goodbye, World! inc1")
+
+            # Included file changed:
+            included.write_text("inc2")
+            wait_for(lambda: read_or_none(out_file) == "Goodbye, World! inc2")
+
+            # Config changed, should reload the context:
+            config.write_text(config.read_text().replace("World", "Earth"))
+            wait_for(lambda: read_or_none(out_file) == "Goodbye, Earth! inc2")
+
+            # New templates should be picked up:
+            new_template = manager.tmpfile(content="New {{ var }}", suffix=".zetch.txt")
+            wait_for(lambda: read_or_none(Path(remove_template(new_template))) == "New Earth")
+        finally:
+            stdout = stop(process)
+
+        assert "Config or extensions changed, reloading." in stdout
+        assert "zetch: 1 template written" in stdout
+
+
+def test_watch_rerenders_dependents_of_templates():
+    """A template included by another should re-render both when changed."""
+    with TmpFileManager() as manager:
+        part = manager.tmpfile(content="part1", full_name="part.zetch.txt")
+        main = manager.tmpfile(content="main {% include 'part.zetch.txt' %}", suffix=".zetch.txt")
+        out_file = Path(remove_template(main))
+
+        process = cli.watch(manager.root_dir, manager.create_cfg({}))
+        try:
+            wait_for(lambda: read_or_none(out_file) == "main part1")
+
+            part.write_text("part2")
+            wait_for(lambda: read_or_none(out_file) == "main part2")
+            assert read_or_none(Path(manager.root_dir).joinpath("part.txt")) == "part2"
+        finally:
+            stop(process)
+
+
+def test_watch_survives_template_errors():
+    """A broken template should be reported, but the watcher should keep going."""
+    with TmpFileManager() as manager:
+        template = manager.tmpfile(content="Hello, {{ var }}!", suffix=".zetch.txt")
+        out_file = Path(remove_template(template))
+
+        process = cli.watch(
+            manager.root_dir,
+            manager.create_cfg({"context": {"static": {"var": {"value": "World"}}}}),
+        )
+        try:
+            wait_for(lambda: read_or_none(out_file) == "Hello, World!")
+
+            template.write_text("Hello, {{ unknown }}!")
+            # Give the watcher time to process the broken template:
+            time.sleep(0.5)
+            assert read_or_none(out_file) == "Hello, World!"
+
+            template.write_text("Fixed, {{ var }}!")
+            wait_for(lambda: read_or_none(out_file) == "Fixed, World!")
+        finally:
+            stdout = stop(process)
+
+        assert "undefined value" in stdout
+
+
+def test_watch_survives_syntax_errors():
+    """A template that fails to parse is reported with its lines, and the watcher keeps going."""
+    with TmpFileManager() as manager:
+        template = manager.tmpfile(content="Hello, {{ var }}!", suffix=".zetch.txt")
+        out_file = Path(remove_template(template))
+
+        process = cli.watch(
+            manager.root_dir,
+            manager.create_cfg({"context": {"static": {"var": {"value": "World"}}}}),
+        )
+        try:
+            wait_for(lambda: read_or_none(out_file) == "Hello, World!")
+
+            template.write_text("Hello, {% if var %}!")
+            time.sleep(0.5)
+            assert read_or_none(out_file) == "Hello, World!"
+
+            template.write_text("Fixed, {{ var }}!")
+            wait_for(lambda: read_or_none(out_file) == "Fixed, World!")
+        finally:
+            stdout = stop(process)
+
+        assert "RenderTemplateError" in stdout
+        assert "Hello, {% if var %}!" in stdout
+
+
+def test_watch_relative_root():
+    """The default root of the current directory works the same as an absolute one."""
+    with TmpFileManager() as manager:
+        template = manager.tmpfile(content="Hello, {{ var }}!", suffix=".zetch.txt")
+        out_file = Path(remove_template(template))
+
+        process = cli.watch(
+            ".",
+            manager.create_cfg({"context": {"static": {"var": {"value": "World"}}}}),
+            cwd=manager.root_dir,
+        )
+        try:
+            wait_for(lambda: read_or_none(out_file) == "Hello, World!")
+
+            template.write_text("Goodbye, {{ var }}!")
+            wait_for(lambda: read_or_none(out_file) == "Goodbye, World!")
+        finally:
+            stop(process)
+
+
+def test_watch_rejects_check():
+    with TmpFileManager() as manager:
+        with pytest.raises(ValueError, match="--check can't be used with watch"):
+            cli.run(
+                [
+                    "zetch",
+                    "watch",
+                    manager.root_dir,
+                    "--config",
+                    str(manager.create_cfg({})),
+                    "--check",
+                ]
+            )
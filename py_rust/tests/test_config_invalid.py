@@ -180,6 +180,19 @@ def test_incorrect_config():
                 ),
             )
 
+        # Engine undefined isn't one of the behaviors:
+        with pytest.raises(
+            ValueError,
+            match=re.escape("[engine.undefined]: Enum conditions are not met."),
+        ):
+            cli.render(
+                manager.root_dir,
+                manager.tmpfile(
+                    "[engine]\nundefined = 'ignore'\n",
+                    suffix=".toml",
+                ),
+            )
+
         # 'env_name' isn't a string:
         with pytest.raises(
             ValueError,
@@ -166,6 +166,16 @@ def cfg_str(config: InputConfig) -> str:
                 "comment_start": "|||/",
                 "comment_end": "/|||",
                 "custom_extensions": [],
+                "template_dirs": [],
+                "relative_includes": False,
+                "preload": [],
+                "trim_blocks": False,
+                "lstrip_blocks": False,
+                "undefined": "strict",
+                "recursion_limit": 500,
+                "fuel": None,
+                "overrides": [],
+                "autoescape": {},
             },
         ),
         # Matchers:
@@ -183,6 +193,42 @@ def cfg_str(config: InputConfig) -> str:
             cfg_str({"matchers": ["foo", "foo-bar_ree", "d77"]}),
             ["foo", "foo-bar_ree", "d77"],
         ),
+        # Match rules:
+        ({}, "match_rules", cfg_str({}), []),
+        (
+            {},
+            "match_rules",
+            cfg_str({"match_rules": [{"pattern": "*.j2"}, {"pattern": "_t_*", "out": "*.out"}]}),
+            [{"pattern": "*.j2", "out": "*"}, {"pattern": "_t_*", "out": "*.out"}],
+        ),
+        # Orphans:
+        ({}, "orphans", cfg_str({}), "delete"),
+        ({}, "orphans", cfg_str({"orphans": "warn"}), "warn"),
+        # Formatters:
+        ({}, "formatters", cfg_str({}), []),
+        (
+            {},
+            "formatters",
+            cfg_str({"formatters": [{"globs": ["*.json"], "command": "prettier --parser json"}]}),
+            [{"globs": ["*.json"], "command": "prettier --parser json"}],
+        ),
+        # Modes:
+        ({}, "modes", cfg_str({}), []),
+        (
+            {},
+            "modes",
+            cfg_str({"modes": [{"globs": ["scripts/*.sh"], "mode": "755"}]}),
+            [{"globs": ["scripts/*.sh"], "mode": "755"}],
+        ),
+        # Validate:
+        ({}, "validate", cfg_str({}), []),
+        ({}, "validate", cfg_str({"validate": ["**/*.json"]}), ["**/*.json"]),
+        # Regions:
+        ({}, "regions", cfg_str({}), []),
+        ({}, "regions", cfg_str({"regions": ["**/*.md"]}), ["**/*.md"]),
+        # Verbatim:
+        ({}, "verbatim", cfg_str({}), []),
+        ({}, "verbatim", cfg_str({"verbatim": ["static.zetch/**"]}), ["static.zetch/**"]),
         # Tasks:
         (
             {},
@@ -1,3 +1,4 @@
+import re
 import typing as tp
 from pathlib import Path
 
@@ -5,6 +6,9 @@ import pytest
 
 from .helpers import cli
 from .helpers.tmp_file_manager import TmpFileManager
+from .helpers.types import MatchRule
+
+RULES: "list[MatchRule]" = [{"pattern": "*.j2"}, {"pattern": "_t_*"}]
 
 
 @pytest.mark.parametrize(
@@ -17,6 +21,12 @@ from .helpers.tmp_file_manager import TmpFileManager
         ("partial_match_should_do_nothing_middle", "etch", "zetch", "foo.zetch.bar", None),
         ("partial_match_should_do_nothing_end", "etch", "zetch", "foo.bar.zetch", None),
         ("arb_file_should_ignore", "etch", "zetch", "arb.txt", None),
+        # Match rules from the config:
+        ("rule_to_matcher", "*.j2", "zetch", "foo.bar.j2", "foo.zetch.bar"),
+        ("rule_to_matcher_no_ext", "*.j2", "zetch", "Makefile.j2", "Makefile.zetch"),
+        ("matcher_to_rule", "zetch", "*.j2", "foo.bar.zetch", "foo.bar.j2"),
+        ("rule_to_rule", "*.j2", "_t_*", "foo.bar.j2", "_t_foo.bar"),
+        ("rule_partial_match_should_do_nothing", "*.j2", "zetch", "foo.j2.bar", None),
     ],
 )
 def test_replace_matcher(
@@ -34,7 +44,7 @@ def test_replace_matcher(
                     old_matcher,
                     new_matcher,
                     "--config",
-                    str(manager.create_cfg({})),
+                    str(manager.create_cfg({"match_rules": RULES})),
                 ],
                 custom_root=Path(manager.root_dir),
                 stdin=stdin,
@@ -60,3 +70,22 @@ def test_replace_matcher(
             new_file = Path(manager.root_dir).joinpath(changes_to)
             assert new_file.exists()
             assert new_file.read_text() == contents
+
+
+def test_replace_matcher_unknown_rule():
+    """Patterns need to be from the config's match_rules, so the naming of their outputs is known."""
+    with TmpFileManager() as manager:
+        manager.tmpfile("", full_name="foo.bar.zetch")
+        with pytest.raises(ValueError, match=re.escape("isn't the pattern of one of the config's")):
+            cli.run(
+                [
+                    "zetch",
+                    "replace-matcher",
+                    "zetch",
+                    "*.tmpl",
+                    "--config",
+                    str(manager.create_cfg({})),
+                ],
+                custom_root=Path(manager.root_dir),
+                stdin="y",
+            )